@@ -1,2 +1,4 @@
 pub type SignedInteger = i32;
 pub type LargeSignedInteger = i64;
+
+pub mod timing;
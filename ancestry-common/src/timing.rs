@@ -0,0 +1,219 @@
+//! Time spent in each phase of a simulation.
+//!
+//! Phases are marked with [phase](crate::phase), and are
+//! only timed when the `timing` feature is enabled.
+//! Phases nest, and each phase is timed separately
+//! for each path of phases that encloses it.
+//! Timing adds a little overhead to every phase, which
+//! matters for short phases that run many times.
+
+use thiserror::Error;
+
+/// Whether phases are timed.
+pub const ENABLED: bool = cfg!(feature = "timing");
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum TimingError {
+    /// The path of the innermost running phase, outermost first.
+    #[error("phases are still running: {0:?}")]
+    PhasesRunning(Vec<&'static str>),
+}
+
+/// The total time spent in a phase.
+#[derive(Clone, Debug)]
+pub struct PhaseTime {
+    /// The names of the enclosing phases, outermost first,
+    /// followed by the name of this phase.
+    pub path: Vec<&'static str>,
+    pub calls: u64,
+    /// Including the time spent in nested phases.
+    pub seconds: f64,
+}
+
+#[cfg(feature = "timing")]
+mod enabled {
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use std::time::{Duration, Instant};
+
+    struct PathData {
+        parent: Option<usize>,
+        name: &'static str,
+        calls: u64,
+        elapsed: Duration,
+    }
+
+    // Each path is stored once and referred to by its index,
+    // so that starting and ending a phase does not allocate.
+    #[derive(Default)]
+    struct Timings {
+        paths: Vec<PathData>,
+        lookup: HashMap<(Option<usize>, &'static str), usize>,
+        stack: Vec<usize>,
+    }
+
+    thread_local! {
+        static TIMINGS: RefCell<Timings> = RefCell::new(Timings::default());
+    }
+
+    /// Times a phase until dropped.
+    pub struct Phase {
+        path: usize,
+        start: Instant,
+    }
+
+    impl Phase {
+        pub fn start(name: &'static str) -> Self {
+            let path = TIMINGS.with(|t| {
+                let mut t = t.borrow_mut();
+                let parent = t.stack.last().copied();
+                let next = t.paths.len();
+                let path = *t.lookup.entry((parent, name)).or_insert(next);
+                if path == next {
+                    t.paths.push(PathData {
+                        parent,
+                        name,
+                        calls: 0,
+                        elapsed: Duration::ZERO,
+                    });
+                }
+                t.stack.push(path);
+                path
+            });
+            Self {
+                path,
+                start: Instant::now(),
+            }
+        }
+    }
+
+    impl Drop for Phase {
+        fn drop(&mut self) {
+            let elapsed = self.start.elapsed();
+            TIMINGS.with(|t| {
+                let mut t = t.borrow_mut();
+                let path = &mut t.paths[self.path];
+                path.calls += 1;
+                path.elapsed += elapsed;
+                let top = t.stack.pop();
+                debug_assert_eq!(top, Some(self.path));
+            });
+        }
+    }
+
+    pub fn take() -> Result<Vec<super::PhaseTime>, super::TimingError> {
+        TIMINGS.with(|t| {
+            let mut t = t.borrow_mut();
+            if !t.stack.is_empty() {
+                let running = t.stack.iter().map(|i| t.paths[*i].name).collect();
+                return Err(super::TimingError::PhasesRunning(running));
+            }
+            let paths = std::mem::take(&mut t.paths);
+            t.lookup.clear();
+
+            // A parent path is always stored before its children,
+            // so sorting by the indexes along each path gives a
+            // depth-first order, with siblings in the order first run.
+            let mut indexes: Vec<Vec<usize>> = vec![];
+            for (i, p) in paths.iter().enumerate() {
+                let mut path = p.parent.map_or(vec![], |parent| indexes[parent].clone());
+                path.push(i);
+                indexes.push(path);
+            }
+            let mut order = (0..paths.len()).collect::<Vec<_>>();
+            order.sort_by(|a, b| indexes[*a].cmp(&indexes[*b]));
+            Ok(order
+                .into_iter()
+                .map(|i| super::PhaseTime {
+                    path: indexes[i].iter().map(|j| paths[*j].name).collect(),
+                    calls: paths[i].calls,
+                    seconds: paths[i].elapsed.as_secs_f64(),
+                })
+                .collect())
+        })
+    }
+}
+
+#[cfg(feature = "timing")]
+pub use enabled::Phase;
+
+/// The phases timed on this thread so far, in depth-first
+/// order, and start timing again from zero.
+/// Empty unless the `timing` feature is enabled.
+///
+/// # Errors
+///
+/// [TimingError::PhasesRunning] if called while a phase is running,
+/// in which case nothing is taken.
+pub fn take() -> Result<Vec<PhaseTime>, TimingError> {
+    #[cfg(feature = "timing")]
+    {
+        enabled::take()
+    }
+    #[cfg(not(feature = "timing"))]
+    {
+        Ok(vec![])
+    }
+}
+
+/// Times the rest of the enclosing block as the phase `name`,
+/// if the `timing` feature is enabled.
+#[cfg(feature = "timing")]
+#[macro_export]
+macro_rules! phase {
+    ($name: expr) => {
+        let _phase = $crate::timing::Phase::start($name);
+    };
+}
+
+/// Times the rest of the enclosing block as the phase `name`,
+/// if the `timing` feature is enabled.
+#[cfg(not(feature = "timing"))]
+#[macro_export]
+macro_rules! phase {
+    ($name: expr) => {};
+}
+
+#[cfg(all(test, feature = "timing"))]
+mod tests {
+    use super::*;
+
+    fn inner() {
+        crate::phase!("inner");
+    }
+
+    #[test]
+    fn test_nested_phases() {
+        {
+            crate::phase!("outer");
+            inner();
+            inner();
+        }
+        inner();
+        let phases = take().unwrap();
+        let paths = phases.iter().map(|p| p.path.clone()).collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            vec![vec!["outer"], vec!["outer", "inner"], vec!["inner"]]
+        );
+        let calls = phases.iter().map(|p| p.calls).collect::<Vec<_>>();
+        assert_eq!(calls, vec![1, 2, 1]);
+        assert!(phases[0].seconds >= phases[1].seconds);
+        assert!(take().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_take_while_running() {
+        {
+            crate::phase!("outer");
+            crate::phase!("inner");
+            assert_eq!(
+                take().unwrap_err(),
+                TimingError::PhasesRunning(vec!["outer", "inner"])
+            );
+        }
+        let phases = take().unwrap();
+        let calls = phases.iter().map(|p| p.calls).collect::<Vec<_>>();
+        assert_eq!(calls, vec![1, 1]);
+    }
+}
@@ -0,0 +1,34 @@
+#![no_main]
+use ancestry_inline_simplification::fuzzing::overlaps;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: Vec<(u8, u8)>| {
+    let intervals = data
+        .iter()
+        .map(|&(left, length)| {
+            let left = left as i64;
+            (left, left + 1 + (length as i64) % 32)
+        })
+        .collect::<Vec<_>>();
+
+    let output = overlaps(&intervals);
+
+    assert!(output.iter().all(|&(left, right, _)| left < right));
+    assert!(output.windows(2).all(|w| w[0].1 <= w[1].0));
+
+    // Positions are small integers, so we can count coverage directly.
+    let mut expected = vec![0_usize; 256 + 32];
+    for &(left, right) in &intervals {
+        for x in left..right {
+            expected[x as usize] += 1;
+        }
+    }
+    let mut observed = vec![0_usize; expected.len()];
+    for &(left, right, n) in &output {
+        assert!(n > 0);
+        for x in left..right {
+            observed[x as usize] = n;
+        }
+    }
+    assert_eq!(expected, observed, "{:?} -> {:?}", intervals, output);
+});
@@ -0,0 +1,82 @@
+#![no_main]
+// Replays arbitrary pedigrees on a Population, which updates
+// ancestry through propagate_ancestry_changes at every step,
+// and checks the resulting graph.
+use ancestry_inline_simplification::Population;
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use neutral_evolution::{ScriptedBirth, TransmittedSegment};
+
+#[derive(Arbitrary, Debug)]
+struct Birth {
+    parent1: u8,
+    parent2: u8,
+    crossovers: Vec<u8>,
+}
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    popsize: u8,
+    genome_length: u8,
+    // Element i of each generation replaces individual i,
+    // unless it is None.
+    generations: Vec<Vec<Option<Birth>>>,
+}
+
+const MAX_POPSIZE: usize = 8;
+const MAX_GENOME_LENGTH: i64 = 32;
+const MAX_GENERATIONS: usize = 16;
+
+// Same rules as neutral_evolution::evolve:
+// a crossover at a position switches parents,
+// and two crossovers at the same position cancel.
+fn transmissions(birth: &Birth, popsize: usize, genome_length: i64) -> Vec<TransmittedSegment> {
+    let mut p1 = birth.parent1 as usize % popsize;
+    let mut p2 = birth.parent2 as usize % popsize;
+    let mut crossovers = birth
+        .crossovers
+        .iter()
+        .filter(|_| genome_length > 1)
+        .map(|&c| 1 + (c as i64) % (genome_length - 1))
+        .collect::<Vec<_>>();
+    crossovers.sort_unstable();
+    crossovers.push(genome_length);
+
+    let mut rv = vec![];
+    let mut left = 0;
+    for w in crossovers.chunk_by(|a, b| a == b) {
+        if w.len() % 2 != 0 || w[0] == genome_length {
+            rv.push(TransmittedSegment::new(left, w[0], p1));
+            left = w[0];
+            std::mem::swap(&mut p1, &mut p2);
+        }
+    }
+    rv
+}
+
+fuzz_target!(|input: Input| {
+    let popsize = 2 + input.popsize as usize % (MAX_POPSIZE - 1);
+    let genome_length = 1 + input.genome_length as i64 % MAX_GENOME_LENGTH;
+    let generations = input
+        .generations
+        .iter()
+        .take(MAX_GENERATIONS)
+        .map(|g| {
+            g.iter()
+                .take(popsize)
+                .enumerate()
+                .filter_map(|(i, b)| {
+                    b.as_ref()
+                        .map(|b| ScriptedBirth::new(i, transmissions(b, popsize, genome_length)))
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    if generations.is_empty() {
+        return;
+    }
+
+    let mut pop = Population::new(popsize as i32, genome_length).unwrap();
+    neutral_evolution::replay(&generations, &mut pop).unwrap();
+    pop.validate_graph().unwrap();
+});
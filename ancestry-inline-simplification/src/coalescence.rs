@@ -0,0 +1,49 @@
+use crate::LargeSignedInteger;
+
+/// Receives changes to coalescences during simplification.
+///
+/// A coalescence is where the ancestry of more than one
+/// lineage overlaps in a node.
+/// A node is revisited when the ancestry below it changes,
+/// which may add, remove, or change its coalescences, so each
+/// event reports how the number of lineages merging in that node
+/// changed over an interval since the previous event covering it.
+/// Intervals that did not change are not reported again.
+///
+/// Applying the events in order reproduces the coalescences
+/// currently in the graph.
+///
+/// Implemented for closures taking the same arguments as
+/// [coalescence](CoalescenceSink::coalescence).
+pub trait CoalescenceSink {
+    /// * `birth_time`: the birth time of the node where lineages merge.
+    /// * `left`, `right`: the half-open genome interval of the change.
+    /// * `previous_num_lineages`: the number of lineages that merged
+    ///   there before, or zero if none did.
+    /// * `num_lineages`: the number of lineages merging there now,
+    ///   or zero if they no longer merge.
+    fn coalescence(
+        &mut self,
+        birth_time: LargeSignedInteger,
+        left: LargeSignedInteger,
+        right: LargeSignedInteger,
+        previous_num_lineages: usize,
+        num_lineages: usize,
+    );
+}
+
+impl<F> CoalescenceSink for F
+where
+    F: FnMut(LargeSignedInteger, LargeSignedInteger, LargeSignedInteger, usize, usize),
+{
+    fn coalescence(
+        &mut self,
+        birth_time: LargeSignedInteger,
+        left: LargeSignedInteger,
+        right: LargeSignedInteger,
+        previous_num_lineages: usize,
+        num_lineages: usize,
+    ) {
+        self(birth_time, left, right, previous_num_lineages, num_lineages)
+    }
+}
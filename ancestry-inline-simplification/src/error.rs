@@ -23,6 +23,10 @@ pub enum InlineAncestryError {
     DeadNode,
     #[error("unreachable child in graph")]
     UnreachableChild,
+    #[error("invalid alive node index: {index:?}")]
+    InvalidAliveNode { index: usize },
+    #[error("mutations recorded before any birth")]
+    NoBirth,
     #[error("{0:?}")]
     TskitError(tskit::TskitError),
 }
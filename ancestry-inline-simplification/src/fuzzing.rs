@@ -0,0 +1,26 @@
+//! Entry points for the fuzz targets in `fuzz/`.
+//!
+//! Not part of the public API.
+//! Run the targets from this crate's directory with,
+//! for example, `cargo fuzz run overlapper`.
+
+use crate::{AncestryIntersection, AncestryOverlapper, LargeSignedInteger, Node};
+
+/// Run the ancestry overlapper over `intervals`,
+/// returning `(left, right, number of overlapping intervals)`
+/// for each step.
+///
+/// # Panics
+///
+/// If any interval has `left < 0` or `right <= left`.
+pub fn overlaps(
+    intervals: &[(LargeSignedInteger, LargeSignedInteger)],
+) -> Vec<(LargeSignedInteger, LargeSignedInteger, usize)> {
+    let intersections = intervals
+        .iter()
+        .map(|&(left, right)| AncestryIntersection::new(left, right, Node::new_alive(0, 1)))
+        .collect::<Vec<_>>();
+    AncestryOverlapper::new(intersections)
+        .map(|(left, right, overlaps)| (left, right, overlaps.borrow().len()))
+        .collect()
+}
@@ -0,0 +1,71 @@
+use crate::node::Node;
+use std::ops::{Add, Sub};
+
+/// Summaries of the ancestry graph of a [Population](crate::Population).
+///
+/// Kept up to date as nodes are born and
+/// ancestry changes are propagated,
+/// rather than by traversing the graph.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct GraphStats {
+    /// Nodes reachable from the alive nodes, including them.
+    pub num_reachable_nodes: usize,
+    /// Ancestry segments over all reachable nodes.
+    pub num_ancestry_segments: usize,
+    /// Child segments over all reachable nodes.
+    pub num_child_segments: usize,
+    /// Reachable nodes with no parents anywhere on the genome.
+    pub num_roots: usize,
+}
+
+impl Add for GraphStats {
+    type Output = Self;
+    fn add(self, rhs: Self) -> Self {
+        Self {
+            num_reachable_nodes: self.num_reachable_nodes + rhs.num_reachable_nodes,
+            num_ancestry_segments: self.num_ancestry_segments + rhs.num_ancestry_segments,
+            num_child_segments: self.num_child_segments + rhs.num_child_segments,
+            num_roots: self.num_roots + rhs.num_roots,
+        }
+    }
+}
+
+impl Sub for GraphStats {
+    type Output = Self;
+    fn sub(self, rhs: Self) -> Self {
+        Self {
+            num_reachable_nodes: self.num_reachable_nodes - rhs.num_reachable_nodes,
+            num_ancestry_segments: self.num_ancestry_segments - rhs.num_ancestry_segments,
+            num_child_segments: self.num_child_segments - rhs.num_child_segments,
+            num_roots: self.num_roots - rhs.num_roots,
+        }
+    }
+}
+
+impl GraphStats {
+    // Once changes have been propagated, a node is reachable
+    // iff it is alive or has children: dead nodes without
+    // children have been removed from their parents.
+    // Summing this over all nodes therefore gives the stats
+    // of the graph, so that updates only need to consider
+    // the nodes whose data change.
+    //
+    // If `ignored_parent` is given, the node is counted
+    // as if that parent were absent.
+    pub(crate) fn contribution(node: &Node, ignored_parent: Option<&Node>) -> Self {
+        let b = node.borrow();
+        if !b.is_alive() && b.children.is_empty() {
+            return Self::default();
+        }
+        let num_parents = match ignored_parent {
+            Some(parent) if b.parents.contains(&parent.downgrade()) => b.parents.len() - 1,
+            _ => b.parents.len(),
+        };
+        Self {
+            num_reachable_nodes: 1,
+            num_ancestry_segments: b.ancestry.len(),
+            num_child_segments: b.children.values().map(|segs| segs.len()).sum(),
+            num_roots: usize::from(num_parents == 0),
+        }
+    }
+}
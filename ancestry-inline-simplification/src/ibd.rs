@@ -0,0 +1,196 @@
+//! Identity by descent between alive nodes.
+//!
+//! We trace the ancestral path of each node up through
+//! `parents` and the `children` segments that link them,
+//! then find, for each position, the youngest ancestor
+//! on all of the paths.
+
+use crate::node::Node;
+use crate::LargeSignedInteger;
+use hashbrown::{HashMap, HashSet};
+use std::collections::BinaryHeap;
+
+/// A segment of genome inherited by a set of
+/// alive nodes from their most recent common ancestor.
+#[derive(Clone, Debug)]
+pub struct IbdSegment {
+    pub left: LargeSignedInteger,
+    pub right: LargeSignedInteger,
+    pub ancestor: Node,
+    pub ancestor_birth_time: LargeSignedInteger,
+}
+
+// Sorted and non-overlapping
+type Segments = Vec<(LargeSignedInteger, LargeSignedInteger)>;
+
+fn squash(mut segments: Segments) -> Segments {
+    segments.sort_unstable();
+    let mut rv: Segments = vec![];
+    for (left, right) in segments {
+        match rv.last_mut() {
+            Some(last) if last.1 >= left => last.1 = last.1.max(right),
+            _ => rv.push((left, right)),
+        }
+    }
+    rv
+}
+
+fn intersect(a: &Segments, b: &Segments) -> Segments {
+    let mut rv = vec![];
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        let left = a[i].0.max(b[j].0);
+        let right = a[i].1.min(b[j].1);
+        if left < right {
+            rv.push((left, right));
+        }
+        if a[i].1 < b[j].1 {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    rv
+}
+
+// Ordered so that the youngest node is popped first.
+// All of a node's descendants are younger than it,
+// so a node's path segments are complete when it is popped.
+struct Pending(Node);
+
+impl PartialEq for Pending {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl Eq for Pending {}
+
+impl PartialOrd for Pending {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Pending {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.0
+            .borrow()
+            .birth_time
+            .cmp(&other.0.borrow().birth_time)
+            .then((self.0.as_ptr() as usize).cmp(&(other.0.as_ptr() as usize)))
+    }
+}
+
+/// The segments of `node`'s genome inherited from each of its ancestors,
+/// including `node` itself.
+fn ancestral_path(node: &Node, genome_length: LargeSignedInteger) -> HashMap<Node, Segments> {
+    let mut pending = HashMap::<Node, Segments>::default();
+    let mut queued = HashSet::<Node>::default();
+    let mut heap = BinaryHeap::new();
+    let mut rv = HashMap::default();
+
+    pending.insert(node.clone(), vec![(0, genome_length)]);
+    queued.insert(node.clone());
+    heap.push(Pending(node.clone()));
+
+    while let Some(Pending(u)) = heap.pop() {
+        let segments = squash(pending.remove(&u).unwrap());
+        for parent in u.borrow().parents.iter() {
+            let parent = parent.upgrade().unwrap();
+            let edges = squash(
+                parent
+                    .borrow()
+                    .children
+                    .get(&u)
+                    .unwrap()
+                    .iter()
+                    .map(|s| (s.left, s.right))
+                    .collect(),
+            );
+            let inherited = intersect(&segments, &edges);
+            if inherited.is_empty() {
+                continue;
+            }
+            pending.entry(parent.clone()).or_default().extend(inherited);
+            if queued.insert(parent.clone()) {
+                heap.push(Pending(parent));
+            }
+        }
+        rv.insert(u, segments);
+    }
+    rv
+}
+
+pub(crate) fn ibd_segments(
+    nodes: &[Node],
+    genome_length: LargeSignedInteger,
+    min_length: LargeSignedInteger,
+) -> Vec<IbdSegment> {
+    let mut paths = nodes.iter().map(|n| ancestral_path(n, genome_length));
+    let mut common = match paths.next() {
+        Some(path) => path,
+        None => return vec![],
+    };
+    for path in paths {
+        common = common
+            .into_iter()
+            .filter_map(|(ancestor, segments)| {
+                let shared = intersect(&segments, path.get(&ancestor)?);
+                if shared.is_empty() {
+                    None
+                } else {
+                    Some((ancestor, shared))
+                }
+            })
+            .collect();
+    }
+
+    // Youngest first: the first common ancestor to claim
+    // a position is the most recent one.
+    let mut candidates = common.into_iter().collect::<Vec<_>>();
+    candidates.sort_by_key(|(a, _)| std::cmp::Reverse(a.borrow().birth_time));
+    let mut claimed: Segments = vec![];
+    let mut rv = vec![];
+    for (ancestor, segments) in candidates {
+        let mut unclaimed = vec![];
+        let mut last = 0;
+        for &(left, right) in claimed.iter() {
+            if left > last {
+                unclaimed.push((last, left));
+            }
+            last = right;
+        }
+        if last < genome_length {
+            unclaimed.push((last, genome_length));
+        }
+        let mrca_segments = intersect(&segments, &unclaimed);
+        if mrca_segments.is_empty() {
+            continue;
+        }
+        let birth_time = ancestor.borrow().birth_time;
+        for &(left, right) in mrca_segments.iter() {
+            rv.push(IbdSegment {
+                left,
+                right,
+                ancestor: ancestor.clone(),
+                ancestor_birth_time: birth_time,
+            });
+        }
+        claimed.extend(mrca_segments);
+        claimed = squash(claimed);
+    }
+
+    rv.sort_by_key(|s| s.left);
+    let mut merged: Vec<IbdSegment> = vec![];
+    for s in rv {
+        match merged.last_mut() {
+            Some(last) if last.right == s.left && last.ancestor == s.ancestor => {
+                last.right = s.right
+            }
+            _ => merged.push(s),
+        }
+    }
+    merged.retain(|s| s.right - s.left >= min_length);
+    merged
+}
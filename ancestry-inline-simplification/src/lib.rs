@@ -1,26 +1,42 @@
 pub use ancestry_common::{LargeSignedInteger, SignedInteger};
 
 mod ancestry_overlapper;
+mod coalescence;
 mod error;
 mod flags;
+mod graph_stats;
+mod ibd;
+mod mutation;
 mod node_heap;
 mod propagate_ancestry_changes;
 mod segments;
+mod sfs;
+mod trees;
 mod update_ancestry;
 mod util;
 
 pub(crate) use ancestry_overlapper::AncestryOverlapper;
 pub(crate) use segments::*;
 
+#[cfg(feature = "fuzzing")]
+#[doc(hidden)]
+pub mod fuzzing;
 pub mod node;
 pub mod population;
 
 // Public API
 // NOTE: this API is TBD, and may later
 // be exported via a pub mod.
+pub use coalescence::CoalescenceSink;
 pub use error::InlineAncestryError;
 pub use flags::NodeFlags;
+pub use graph_stats::GraphStats;
+pub use ibd::IbdSegment;
+pub use mutation::Mutation;
+pub use node::num_live_nodes;
 pub use node::Node;
 pub use node::NodeData;
+pub use node::WeakNode;
 pub use node_heap::NodeHeap;
 pub use population::Population;
+pub use trees::{LocalTree, LocalTrees};
@@ -0,0 +1,22 @@
+use crate::LargeSignedInteger;
+
+/// A neutral mutation on the branch above a node.
+///
+/// Mutations move to descendant nodes as their original node
+/// is simplified out of the graph, so `birth_time` refers to the
+/// node that the mutation arose on, which may be an ancestor
+/// of the node that currently holds it.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Mutation {
+    pub position: f64,
+    pub birth_time: LargeSignedInteger,
+}
+
+impl Mutation {
+    pub fn new(position: f64, birth_time: LargeSignedInteger) -> Self {
+        Self {
+            position,
+            birth_time,
+        }
+    }
+}
@@ -1,11 +1,15 @@
 use crate::InlineAncestryError;
 use crate::{
-    AncestrySegment, HalfOpenInterval, LargeSignedInteger, NodeFlags, Segment, SignedInteger,
+    AncestrySegment, HalfOpenInterval, LargeSignedInteger, Mutation, NodeFlags, Segment,
+    SignedInteger,
 };
 use std::fmt::Debug;
 use std::hash::{Hash, Hasher};
-use std::rc::Rc;
-use std::{cell::RefCell, ops::Deref};
+use std::rc::{Rc, Weak};
+use std::{
+    cell::{Cell, RefCell},
+    ops::Deref,
+};
 
 // Use this over std::collections b/c the hashing
 // fn is much faster. (We aren't doing cryptography.)
@@ -22,8 +26,54 @@ use hashbrown::{HashMap, HashSet};
 #[derive(Clone)]
 pub struct Node(Rc<RefCell<NodeData>>);
 
+/// A non-owning pointer to [NodeData](NodeData).
+///
+/// Parents are stored as `WeakNode` so that parents and
+/// children do not own each other.
+/// Hashing and equality are with respect to the pointers,
+/// as for [Node](Node).
+#[derive(Clone)]
+pub struct WeakNode(Weak<RefCell<NodeData>>);
+
 pub type ChildMap = HashMap<Node, Vec<Segment>>;
-pub type ParentSet = HashSet<Node>;
+pub type ParentSet = HashSet<WeakNode>;
+
+thread_local! {
+    static NUM_LIVE_NODES: Cell<usize> = const { Cell::new(0) };
+}
+
+/// The number of [NodeData](NodeData) allocated on
+/// this thread and not yet dropped.
+///
+/// For finding leaks: once a [Population](crate::Population)
+/// has simplified, this should equal
+/// [num_still_reachable](crate::Population::num_still_reachable),
+/// and zero once it is dropped.
+pub fn num_live_nodes() -> usize {
+    NUM_LIVE_NODES.with(|n| n.get())
+}
+
+// Counts NodeData allocations for num_live_nodes.
+struct LiveNode;
+
+impl LiveNode {
+    fn new() -> Self {
+        NUM_LIVE_NODES.with(|n| n.set(n.get() + 1));
+        Self
+    }
+}
+
+impl Clone for LiveNode {
+    fn clone(&self) -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for LiveNode {
+    fn drop(&mut self) {
+        NUM_LIVE_NODES.with(|n| n.set(n.get() - 1));
+    }
+}
 
 #[derive(Clone)] // NOTE: this does not have to be Clone b/c we work via pointers
 pub struct NodeData {
@@ -33,6 +83,13 @@ pub struct NodeData {
     pub parents: ParentSet,
     pub ancestry: Vec<AncestrySegment>,
     pub children: ChildMap,
+    pub mutations: Vec<Mutation>,
+    // The indexes of the parents at birth.
+    pub pedigree_parents: Vec<SignedInteger>,
+    // The coalescences last reported to a CoalescenceSink,
+    // as (left, right, num_lineages).
+    pub(crate) reported_coalescences: Vec<(LargeSignedInteger, LargeSignedInteger, usize)>,
+    _live: LiveNode,
 }
 
 impl Debug for Node {
@@ -65,7 +122,47 @@ impl Hash for Node {
     }
 }
 
+impl PartialEq for WeakNode {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self.0.as_ptr(), other.0.as_ptr())
+    }
+}
+
+impl Eq for WeakNode {}
+
+impl Hash for WeakNode {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.as_ptr().hash(state);
+    }
+}
+
+impl Debug for WeakNode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.upgrade() {
+            Some(node) => node.fmt(f),
+            None => f.write_str("WeakNode(dropped)"),
+        }
+    }
+}
+
+impl WeakNode {
+    /// `None` if the node has been dropped.
+    ///
+    /// Parents are owned by the children of their own parents.
+    /// Roots are owned by the [Population](crate::Population):
+    /// alive ones as its nodes, and dead ones because they are
+    /// coalescences. So the parents of a node in the graph are
+    /// never dropped.
+    pub fn upgrade(&self) -> Option<Node> {
+        self.0.upgrade().map(Node)
+    }
+}
+
 impl Node {
+    pub fn downgrade(&self) -> WeakNode {
+        WeakNode(Rc::downgrade(&self.0))
+    }
+
     pub fn new_alive(index: SignedInteger, birth_time: LargeSignedInteger) -> Self {
         Self(Rc::new(RefCell::<NodeData>::new(NodeData::new_alive(
             index, birth_time,
@@ -83,7 +180,7 @@ impl Node {
         let rv = Self::new_alive(index, birth_time);
         rv.borrow_mut()
             .ancestry
-            .push(AncestrySegment::new(0, genome_length, rv.clone()));
+            .push(AncestrySegment::new_mapping_to_self(0, genome_length));
         rv
     }
 
@@ -98,7 +195,7 @@ impl Node {
     pub fn add_parent(&mut self, parent: Node) -> Result<(), InlineAncestryError> {
         let mut sb = self.borrow_mut();
         if sb.birth_time > parent.borrow().birth_time {
-            sb.parents.insert(parent);
+            sb.parents.insert(parent.downgrade());
             Ok(())
         } else {
             Err(InlineAncestryError::InvalidBirthTimeOrder {
@@ -127,8 +224,11 @@ impl Node {
 
     #[inline(never)]
     // TODO: instead of pup(crate), this should perhaps be standalone?
-    pub(crate) fn update_ancestry(&mut self) -> Result<bool, InlineAncestryError> {
-        let rv = crate::update_ancestry::update_ancestry(self);
+    pub(crate) fn update_ancestry(
+        &mut self,
+        coalescence_sink: &mut Option<Box<dyn crate::CoalescenceSink>>,
+    ) -> Result<bool, InlineAncestryError> {
+        let rv = crate::update_ancestry::update_ancestry(self, coalescence_sink);
         Ok(rv)
     }
 
@@ -152,6 +252,10 @@ impl NodeData {
             parents: ParentSet::default(),
             ancestry: vec![],
             children: ChildMap::default(),
+            mutations: vec![],
+            pedigree_parents: vec![],
+            reported_coalescences: vec![],
+            _live: LiveNode::new(),
         }
     }
 
@@ -177,12 +281,12 @@ mod practice_tests {
     use super::*;
 
     fn remove_parent(parent: Node, child: Node) {
-        child.borrow_mut().parents.remove(&parent);
+        child.borrow_mut().parents.remove(&parent.downgrade());
     }
 
     // Better -- does not increase ref counts just for fn call.
     fn remove_parent_via_ref(parent: &Node, child: &Node) {
-        child.borrow_mut().parents.remove(&parent);
+        child.borrow_mut().parents.remove(&parent.downgrade());
     }
 
     #[test]
@@ -201,11 +305,14 @@ mod practice_tests {
             let p = pop[0].clone();
             pop[1].add_parent(p).unwrap();
         }
-        assert_eq!(Rc::strong_count(&pop[0]), 2);
+        // Parents are not owned by their children.
+        assert_eq!(Rc::strong_count(&pop[0]), 1);
+        assert_eq!(Rc::weak_count(&pop[0]), 1);
         assert_eq!(Rc::strong_count(&pop[1]), 2);
 
         remove_parent(pop[0].clone(), pop[1].clone());
         assert_eq!(Rc::strong_count(&pop[0]), 1);
+        assert_eq!(Rc::weak_count(&pop[0]), 0);
         assert_eq!(Rc::strong_count(&pop[1]), 2);
     }
 
@@ -225,11 +332,14 @@ mod practice_tests {
             let p = pop[0].clone();
             pop[1].add_parent(p).unwrap();
         }
-        assert_eq!(Rc::strong_count(&pop[0]), 2);
+        // Parents are not owned by their children.
+        assert_eq!(Rc::strong_count(&pop[0]), 1);
+        assert_eq!(Rc::weak_count(&pop[0]), 1);
         assert_eq!(Rc::strong_count(&pop[1]), 2);
 
         remove_parent_via_ref(&pop[0], &pop[1]);
         assert_eq!(Rc::strong_count(&pop[0]), 1);
+        assert_eq!(Rc::weak_count(&pop[0]), 0);
         assert_eq!(Rc::strong_count(&pop[1]), 2);
     }
 }
@@ -242,7 +352,9 @@ mod tests {
     fn test_alive_node_has_ancestry_to_self() {
         let node = Node::new_alive_with_ancestry_mapping_to_self(0, 0, 10);
         assert_eq!(node.borrow().ancestry.len(), 1);
-        assert!(node.borrow().ancestry[0].child == node);
+        assert!(node.borrow().ancestry[0].mapped_node(&node) == node);
+        // No reference cycle
+        assert_eq!(std::rc::Rc::strong_count(&node), 1);
     }
 
     #[test]
@@ -48,6 +48,10 @@ impl PrioritizedNode {
         self.node
     }
 
+    pub(crate) fn node(&self) -> &Node {
+        &self.node
+    }
+
     fn is_death(&self) -> bool {
         matches!(self.node_type, NodeType::Death)
     }
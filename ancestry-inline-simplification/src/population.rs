@@ -14,6 +14,17 @@ pub struct Population {
     births: Vec<Node>,
     next_replacement: usize,
     node_heap: NodeHeap,
+    coalescence_sink: Option<Box<dyn crate::CoalescenceSink>>,
+    stats: crate::GraphStats,
+    // The nodes changed by the last simplification,
+    // recorded in debug builds for validation.
+    touched: Vec<Node>,
+    provenance: Option<neutral_evolution::Provenance>,
+    // Every dead node with ancestry that maps to itself, i.e. the
+    // coalescences, whether or not it has parents.
+    // Children only hold weak references to their parents,
+    // so this is what keeps the dead roots alive.
+    coalescences: HashSet<Node>,
     pub nodes: Vec<Node>,
 }
 
@@ -39,6 +50,16 @@ impl Population {
                 births: vec![],
                 next_replacement: 0,
                 node_heap: NodeHeap::default(),
+                coalescence_sink: None,
+                stats: crate::GraphStats {
+                    num_reachable_nodes: nodes.len(),
+                    num_ancestry_segments: nodes.len(),
+                    num_child_segments: 0,
+                    num_roots: nodes.len(),
+                },
+                touched: vec![],
+                provenance: None,
+                coalescences: HashSet::new(),
                 nodes,
             })
         } else {
@@ -73,13 +94,85 @@ impl Population {
         crate::util::all_reachable_nodes(&self.nodes)
     }
 
+    /// The number of nodes reachable from the alive nodes,
+    /// including births recorded since the last simplification.
+    ///
+    /// This does not traverse the graph.
     pub fn num_still_reachable(&self) -> usize {
-        self.all_reachable_nodes().len()
+        self.stats.num_reachable_nodes
     }
 
     pub fn validate_graph(&self) -> Result<(), InlineAncestryError> {
         crate::util::validate_graph(&self.nodes, self.genome_length)
     }
+
+    /// Summaries of the ancestry graph, including births
+    /// recorded since the last simplification.
+    pub fn graph_stats(&self) -> crate::GraphStats {
+        self.stats
+    }
+
+    /// Report coalescences to `sink` during simplification,
+    /// replacing any previous sink.
+    pub fn set_coalescence_sink(&mut self, sink: Box<dyn crate::CoalescenceSink>) {
+        self.coalescence_sink = Some(sink);
+    }
+
+    /// Stop reporting coalescences, returning the current sink.
+    pub fn take_coalescence_sink(&mut self) -> Option<Box<dyn crate::CoalescenceSink>> {
+        self.coalescence_sink.take()
+    }
+
+    /// The segments that the alive nodes at `indexes` inherit from
+    /// their most recent common ancestors, in order of position.
+    ///
+    /// Adjacent segments from the same ancestor are merged, and then
+    /// segments shorter than `min_length` are discarded.
+    /// Each segment's TMRCA is the current time minus
+    /// its `ancestor_birth_time`.
+    ///
+    /// # Errors
+    ///
+    /// [InvalidAliveNode](InlineAncestryError::InvalidAliveNode)
+    /// if an index is out of range.
+    pub fn ibd_segments(
+        &self,
+        indexes: &[usize],
+        min_length: LargeSignedInteger,
+    ) -> Result<Vec<crate::IbdSegment>, InlineAncestryError> {
+        let mut nodes = vec![];
+        for &index in indexes {
+            match self.nodes.get(index) {
+                Some(node) => nodes.push(node.clone()),
+                None => return Err(InlineAncestryError::InvalidAliveNode { index }),
+            }
+        }
+        Ok(crate::ibd::ibd_segments(
+            &nodes,
+            self.genome_length,
+            min_length,
+        ))
+    }
+
+    /// Iterate over the local trees of the alive nodes,
+    /// from left to right.
+    pub fn trees(&self) -> crate::LocalTrees {
+        crate::LocalTrees::new(&self.nodes, self.genome_length)
+    }
+
+    /// The branch-mode site frequency spectrum of the alive nodes.
+    ///
+    /// Entry `k` is the total span × length of branches with `k`
+    /// alive nodes below them.  If `polarised` is false, entries
+    /// `k` and `n - k` are combined into the smaller of the two.
+    /// If `span_normalise` is true, the result is divided by the
+    /// genome length.
+    ///
+    /// Matches tskit's `allele_frequency_spectrum` in branch mode,
+    /// applied to the tables made by `TryFrom<Population>`.
+    pub fn branch_sfs(&self, polarised: bool, span_normalise: bool) -> Vec<f64> {
+        crate::sfs::branch_sfs(&self.nodes, self.genome_length, polarised, span_normalise)
+    }
 }
 
 impl EvolveAncestry for Population {
@@ -120,6 +213,11 @@ impl EvolveAncestry for Population {
             // Increase ref count of parent
             let mut parent = self.get_mut(b.parent).as_mut().unwrap().clone();
 
+            let index = parent.borrow().index;
+            if !birth.borrow().pedigree_parents.contains(&index) {
+                birth.borrow_mut().pedigree_parents.push(index);
+            }
+
             // Add references to birth for each segment
             parent.add_child_segment(b.left, b.right, birth.clone())?;
             // MOVE parent w/o increasing ref count
@@ -128,11 +226,46 @@ impl EvolveAncestry for Population {
 
         assert!(!birth.borrow().parents.is_empty());
 
+        // The parents are alive, so only their
+        // child segments change.
+        self.stats = self.stats
+            + crate::GraphStats::contribution(&birth, None)
+            + crate::GraphStats {
+                num_child_segments: breakpoints.len(),
+                ..Default::default()
+            };
+
         // MOVE the birth w/o increasing ref count
         self.births.push(birth);
         Ok(())
     }
 
+    fn record_mutations(
+        &mut self,
+        birth_time: LargeSignedInteger,
+        _final_time: LargeSignedInteger,
+        mutations: &[neutral_evolution::Mutation],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let birth = self
+            .births
+            .last()
+            .ok_or(crate::InlineAncestryError::NoBirth)?;
+        birth.borrow_mut().mutations.extend(
+            mutations
+                .iter()
+                .map(|m| crate::Mutation::new(m.position, birth_time)),
+        );
+        Ok(())
+    }
+
+    fn record_provenance(
+        &mut self,
+        provenance: &neutral_evolution::Provenance,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.provenance = Some(*provenance);
+        Ok(())
+    }
+
     fn simplify(
         &mut self,
         current_time_point: LargeSignedInteger,
@@ -156,12 +289,17 @@ impl EvolveAncestry for Population {
         let _poppped = crate::propagate_ancestry_changes::propagate_ancestry_changes(
             self.genome_length,
             &mut self.node_heap,
+            &mut self.coalescence_sink,
+            &mut self.stats,
+            &mut self.touched,
+            &mut self.coalescences,
         )?;
 
         #[cfg(debug_assertions)]
         {
-            self.validate_graph()?;
+            crate::util::validate_nodes(&self.touched, self.genome_length)?;
         }
+        self.touched.clear();
 
         assert!(self.node_heap.is_empty());
         Ok(())
@@ -184,26 +322,59 @@ impl TryFrom<Population> for tskit::TableCollection {
             Err(e) => return Err(crate::InlineAncestryError::TskitError(e)),
         };
 
-        let mut node_map = std::collections::HashMap::<_, _>::default();
-        let mut max_time: LargeSignedInteger = 0;
+        let reachable = value.all_reachable_nodes();
+        let max_time = reachable
+            .iter()
+            .fold(0, |m, i| std::cmp::max(m, i.borrow().birth_time));
 
-        for i in value.all_reachable_nodes() {
-            max_time = std::cmp::max(max_time, i.borrow().birth_time);
-            node_map.insert(i.clone(), tskit::NodeId::NULL);
+        let population = match tables.add_population() {
+            Ok(population) => population,
+            Err(e) => return Err(crate::InlineAncestryError::TskitError(e)),
+        };
+
+        // Alive nodes come first, in population order, so that
+        // sample IDs can be compared to the output of other backends.
+        let mut nodes = vec![];
+        let mut seen = HashSet::new();
+        for i in value.nodes.iter().chain(reachable.iter()) {
+            if seen.insert(i.clone()) {
+                nodes.push(i.clone());
+            }
         }
 
-        for (k, v) in node_map.iter_mut() {
-            let birth_time = (-1_i64 * (k.borrow().birth_time - max_time)) as f64;
-            *v = match tables.add_node(0, birth_time, -1, -1) {
-                Ok(node_id) => node_id,
+        // Each node is a (haploid) individual. Individuals are added
+        // in birth order, so that parents come before their children.
+        // Parents that are not in the tables are NULL.
+        let mut by_index = nodes.iter().collect::<Vec<_>>();
+        by_index.sort_by_key(|i| i.borrow().index);
+        let mut individual_map = std::collections::HashMap::<_, _>::default();
+        for i in by_index {
+            let parents = i
+                .borrow()
+                .pedigree_parents
+                .iter()
+                .map(|p| *individual_map.get(p).unwrap_or(&tskit::IndividualId::NULL))
+                .collect::<Vec<_>>();
+            match tables.add_individual(0, &[] as &[f64], &parents) {
+                Ok(individual) => individual_map.insert(i.borrow().index, individual),
                 Err(e) => return Err(crate::InlineAncestryError::TskitError(e)),
             };
         }
 
-        for i in value.all_reachable_nodes() {
-            let pid = node_map.get(&i).unwrap();
+        let mut node_map = std::collections::HashMap::<_, _>::default();
+        for i in nodes {
+            let birth_time = (-1_i64 * (i.borrow().birth_time - max_time)) as f64;
+            let individual = individual_map[&i.borrow().index];
+            match tables.add_node(0, birth_time, population, individual) {
+                Ok(node_id) => node_map.insert(i, node_id),
+                Err(e) => return Err(crate::InlineAncestryError::TskitError(e)),
+            };
+        }
+
+        for i in reachable.iter() {
+            let pid = node_map.get(i).unwrap();
             for (k, v) in i.borrow().children.iter() {
-                let cid = node_map.get(&k).unwrap();
+                let cid = node_map.get(k).unwrap();
                 for j in v {
                     match tables.add_edge(j.left as f64, j.right as f64, *pid, *cid) {
                         Ok(_) => (),
@@ -213,11 +384,41 @@ impl TryFrom<Population> for tskit::TableCollection {
             }
         }
 
+        // Sites are shared by all mutations at the same position.
+        let mut mutations = vec![];
+        for i in reachable.iter() {
+            let node = node_map.get(i).unwrap();
+            for m in i.borrow().mutations.iter() {
+                mutations.push((m.position, (max_time - m.birth_time) as f64, *node));
+            }
+        }
+        mutations.sort_by(|a, b| a.0.total_cmp(&b.0).then(b.1.total_cmp(&a.1)));
+        let mut site = (f64::NAN, tskit::SiteId::NULL);
+        for (position, time, node) in mutations {
+            if position != site.0 {
+                site = match tables.add_site(position, Some(b"0")) {
+                    Ok(site_id) => (position, site_id),
+                    Err(e) => return Err(crate::InlineAncestryError::TskitError(e)),
+                };
+            }
+            match tables.add_mutation(site.1, node, tskit::MutationId::NULL, time, Some(b"1")) {
+                Ok(_) => (),
+                Err(e) => return Err(crate::InlineAncestryError::TskitError(e)),
+            }
+        }
+
         for i in value.nodes.iter() {
             let node = node_map.get(i).unwrap();
             tables.nodes().flags_array_mut()[usize::from(*node)] = tskit::NodeFlags::IS_SAMPLE;
         }
 
+        if let Some(provenance) = value.provenance.as_ref() {
+            let record = provenance.to_json(env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+            if let Err(e) = tskit::provenance::Provenance::add_provenance(&mut tables, &record) {
+                return Err(crate::InlineAncestryError::TskitError(e));
+            }
+        }
+
         match tables.full_sort(tskit::TableSortOptions::default()) {
             Ok(_) => (),
             Err(e) => return Err(crate::InlineAncestryError::TskitError(e)),
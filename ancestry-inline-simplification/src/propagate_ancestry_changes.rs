@@ -2,15 +2,31 @@ use crate::node::Node;
 use crate::node_heap::NodeHeap;
 use crate::segments::AncestrySegment;
 use crate::segments::HalfOpenInterval;
+use crate::GraphStats;
 use crate::InlineAncestryError;
+use hashbrown::HashSet;
 
 pub fn propagate_ancestry_changes(
     genome_length: crate::LargeSignedInteger,
     node_heap: &mut NodeHeap,
+    coalescence_sink: &mut Option<Box<dyn crate::CoalescenceSink>>,
+    stats: &mut GraphStats,
+    touched: &mut Vec<Node>,
+    coalescences: &mut HashSet<Node>,
 ) -> Result<i32, InlineAncestryError> {
+    ancestry_common::phase!("propagate_ancestry_changes");
     let mut popped = 0;
+    // Reused to avoid allocating for every node.
+    let mut old_children = HashSet::<Node>::default();
     while let Some(mut n) = node_heap.pop() {
         popped += 1;
+        // Updating a node changes its own data and the
+        // parents of its children, before and after.
+        old_children.extend(n.node().borrow().children.keys().cloned());
+        let mut before = GraphStats::contribution(n.node(), None);
+        for child in old_children.iter() {
+            before = before + GraphStats::contribution(child, None);
+        }
         n.preprocess(genome_length);
         let mut node = Node::from(n);
         #[cfg(debug_assertions)]
@@ -22,7 +38,30 @@ pub fn propagate_ancestry_changes(
             ancestry
         };
 
-        let changed = node.update_ancestry()?;
+        let changed = node.update_ancestry(coalescence_sink)?;
+        if !node.is_alive() && node.borrow().ancestry.iter().any(|a| a.child.is_none()) {
+            coalescences.insert(node.clone());
+        } else {
+            coalescences.remove(&node);
+        }
+
+        let mut after = GraphStats::contribution(&node, None);
+        for child in old_children.iter() {
+            after = after + GraphStats::contribution(child, None);
+        }
+        for child in node.borrow().children.keys() {
+            if !old_children.contains(child) {
+                before = before + GraphStats::contribution(child, Some(&node));
+                after = after + GraphStats::contribution(child, None);
+            }
+        }
+        *stats = *stats + after - before;
+
+        if cfg!(debug_assertions) {
+            touched.push(node.clone());
+            touched.extend(old_children.iter().cloned());
+            touched.extend(node.borrow().children.keys().cloned());
+        }
 
         #[cfg(debug_assertions)]
         {
@@ -87,9 +126,10 @@ pub fn propagate_ancestry_changes(
         if changed {
             //|| node.is_alive() {
             for parent in node.borrow_mut().parents.iter() {
-                node_heap.push_parent(parent.clone());
+                node_heap.push_parent(parent.upgrade().unwrap());
             }
         }
+        old_children.clear();
     }
     assert!(node_heap.is_empty());
     Ok(popped)
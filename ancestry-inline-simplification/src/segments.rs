@@ -43,16 +43,32 @@ impl Segment {
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct AncestrySegment {
     pub segment: Segment,
-    pub child: Node,
+    /// `None` if the segment maps to the node that it belongs to.
+    /// Storing that node would be a reference cycle, which
+    /// would never be freed.
+    pub child: Option<Node>,
 }
 
 impl AncestrySegment {
     pub fn new(left: LargeSignedInteger, right: LargeSignedInteger, child: Node) -> Self {
         Self {
             segment: Segment::new_unchecked(left, right),
-            child,
+            child: Some(child),
         }
     }
+
+    pub fn new_mapping_to_self(left: LargeSignedInteger, right: LargeSignedInteger) -> Self {
+        Self {
+            segment: Segment::new_unchecked(left, right),
+            child: None,
+        }
+    }
+
+    /// The node that the segment maps to, where `owner`
+    /// is the node that the segment belongs to.
+    pub fn mapped_node(&self, owner: &Node) -> Node {
+        self.child.as_ref().unwrap_or(owner).clone()
+    }
 }
 
 #[derive(Clone, Eq, Debug, PartialEq)]
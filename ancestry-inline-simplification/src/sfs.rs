@@ -0,0 +1,100 @@
+//! Branch-mode site frequency spectrum, computed on the graph.
+//!
+//! Each edge contributes span × branch length to the entry for
+//! the number of alive nodes below it.
+//! This is the same as tskit's `allele_frequency_spectrum`
+//! in branch mode for the exported tables, but does not
+//! require building them.
+
+use crate::node::Node;
+use crate::LargeSignedInteger;
+use std::collections::BTreeMap;
+
+// Piecewise-constant number of alive descendants:
+// (left, right, count), sorted and tiling the genome.
+type Counts = Vec<(LargeSignedInteger, LargeSignedInteger, usize)>;
+
+fn alive_descendants(
+    node: &Node,
+    genome_length: LargeSignedInteger,
+    counts: &hashbrown::HashMap<Node, Counts>,
+) -> Counts {
+    let mut deltas = BTreeMap::<LargeSignedInteger, i64>::new();
+    deltas.insert(0, 0);
+    deltas.insert(genome_length, 0);
+    let mut add = |left, right, count: usize| {
+        *deltas.entry(left).or_default() += count as i64;
+        *deltas.entry(right).or_default() -= count as i64;
+    };
+    if node.is_alive() {
+        add(0, genome_length, 1);
+    }
+    for (child, segs) in node.borrow().children.iter() {
+        let child_counts = counts.get(child).unwrap();
+        for seg in segs {
+            for &(left, right, count) in child_counts.iter() {
+                if right > seg.left && seg.right > left {
+                    add(left.max(seg.left), right.min(seg.right), count);
+                }
+            }
+        }
+    }
+
+    let mut rv = vec![];
+    let mut current = 0;
+    let mut last = 0;
+    for (position, delta) in deltas {
+        if position > last {
+            rv.push((last, position, current as usize));
+        }
+        current += delta;
+        last = position;
+    }
+    rv
+}
+
+/// See [Population::branch_sfs](crate::Population::branch_sfs).
+pub(crate) fn branch_sfs(
+    nodes: &[Node],
+    genome_length: LargeSignedInteger,
+    polarised: bool,
+    span_normalise: bool,
+) -> Vec<f64> {
+    let num_samples = nodes.len();
+    let mut reachable = crate::util::all_reachable_nodes(nodes)
+        .into_iter()
+        .collect::<Vec<_>>();
+    // Children are born after their parents.
+    reachable.sort_by_key(|n| std::cmp::Reverse(n.borrow().birth_time));
+
+    let mut sfs = vec![0.0; num_samples + 1];
+    let mut counts = hashbrown::HashMap::<Node, Counts>::default();
+    for node in reachable {
+        let node_counts = alive_descendants(&node, genome_length, &counts);
+        let birth_time = node.borrow().birth_time;
+        for (child, segs) in node.borrow().children.iter() {
+            let branch_length = (child.borrow().birth_time - birth_time) as f64;
+            for seg in segs {
+                for &(left, right, count) in counts.get(child).unwrap().iter() {
+                    if right > seg.left && seg.right > left {
+                        let span = (right.min(seg.right) - left.max(seg.left)) as f64;
+                        let k = if !polarised && 2 * count > num_samples {
+                            num_samples - count
+                        } else {
+                            count
+                        };
+                        sfs[k] += span * branch_length;
+                    }
+                }
+            }
+        }
+        counts.insert(node, node_counts);
+    }
+
+    if span_normalise {
+        for x in sfs.iter_mut() {
+            *x /= genome_length as f64;
+        }
+    }
+    sfs
+}
@@ -0,0 +1,165 @@
+//! Local trees, built directly from the graph.
+//!
+//! Nodes are numbered with the alive nodes first,
+//! in population order, followed by the other reachable nodes.
+
+use crate::node::Node;
+use crate::LargeSignedInteger;
+use tskit::StreamingIterator;
+
+/// The genealogy of one interval of the genome.
+#[derive(Clone, Debug)]
+pub struct LocalTree {
+    left: LargeSignedInteger,
+    right: LargeSignedInteger,
+    parents: Vec<Option<usize>>,
+    birth_times: Vec<LargeSignedInteger>,
+    num_samples: usize,
+}
+
+impl LocalTree {
+    /// The half-open genomic interval of this tree.
+    pub fn interval(&self) -> (LargeSignedInteger, LargeSignedInteger) {
+        (self.left, self.right)
+    }
+
+    pub fn parent(&self, node: usize) -> Option<usize> {
+        self.parents[node]
+    }
+
+    pub fn parents(&self) -> &[Option<usize>] {
+        &self.parents
+    }
+
+    pub fn birth_time(&self, node: usize) -> LargeSignedInteger {
+        self.birth_times[node]
+    }
+
+    pub fn num_nodes(&self) -> usize {
+        self.parents.len()
+    }
+
+    /// The alive nodes, which are `0..num_samples`.
+    pub fn samples(&self) -> std::ops::Range<usize> {
+        0..self.num_samples
+    }
+
+    /// The sum of all branch lengths, not weighted by span.
+    pub fn total_branch_length(&self) -> LargeSignedInteger {
+        self.parents
+            .iter()
+            .enumerate()
+            .filter_map(|(u, p)| p.map(|p| self.birth_times[u] - self.birth_times[p]))
+            .sum()
+    }
+}
+
+/// Iterates over [LocalTree] from left to right.
+///
+/// Made by [Population::trees](crate::Population::trees).
+/// Like the trees of a [tskit::TreeSequence], this is a
+/// [StreamingIterator]: each tree is updated in place,
+/// and only borrowed.
+pub struct LocalTrees {
+    genome_length: LargeSignedInteger,
+    // (left, right, parent, child)
+    insertion: Vec<(LargeSignedInteger, LargeSignedInteger, usize, usize)>,
+    removal: Vec<(LargeSignedInteger, LargeSignedInteger, usize, usize)>,
+    next_insertion: usize,
+    next_removal: usize,
+    position: LargeSignedInteger,
+    tree: LocalTree,
+    // Whether tree is the current one, rather than
+    // before the first or after the last.
+    valid: bool,
+}
+
+impl LocalTrees {
+    pub(crate) fn new(alive: &[Node], genome_length: LargeSignedInteger) -> Self {
+        let reachable = crate::util::all_reachable_nodes(alive);
+        let mut index = hashbrown::HashMap::<Node, usize>::default();
+        let mut birth_times = vec![];
+        for node in alive.iter().chain(reachable.iter()) {
+            if !index.contains_key(node) {
+                index.insert(node.clone(), birth_times.len());
+                birth_times.push(node.borrow().birth_time);
+            }
+        }
+
+        let mut insertion = vec![];
+        for node in reachable.iter() {
+            let parent = index[node];
+            for (child, segs) in node.borrow().children.iter() {
+                for seg in segs {
+                    insertion.push((seg.left, seg.right, parent, index[child]));
+                }
+            }
+        }
+        let mut removal = insertion.clone();
+        insertion.sort_unstable_by_key(|e| e.0);
+        removal.sort_unstable_by_key(|e| e.1);
+
+        let tree = LocalTree {
+            left: 0,
+            right: 0,
+            parents: vec![None; birth_times.len()],
+            birth_times,
+            num_samples: alive.len(),
+        };
+
+        Self {
+            genome_length,
+            insertion,
+            removal,
+            next_insertion: 0,
+            next_removal: 0,
+            position: 0,
+            tree,
+            valid: false,
+        }
+    }
+}
+
+impl StreamingIterator for LocalTrees {
+    type Item = LocalTree;
+
+    fn advance(&mut self) {
+        self.valid = self.position < self.genome_length;
+        if !self.valid {
+            return;
+        }
+        while let Some(&(_, right, _, child)) = self.removal.get(self.next_removal) {
+            if right != self.position {
+                break;
+            }
+            self.tree.parents[child] = None;
+            self.next_removal += 1;
+        }
+        while let Some(&(left, _, parent, child)) = self.insertion.get(self.next_insertion) {
+            if left != self.position {
+                break;
+            }
+            self.tree.parents[child] = Some(parent);
+            self.next_insertion += 1;
+        }
+
+        let mut right = self.genome_length;
+        if let Some(e) = self.insertion.get(self.next_insertion) {
+            right = right.min(e.0);
+        }
+        if let Some(e) = self.removal.get(self.next_removal) {
+            right = right.min(e.1);
+        }
+        self.tree.left = self.position;
+        self.tree.right = right;
+        self.position = right;
+    }
+
+    fn get(&self) -> Option<&Self::Item> {
+        if self.valid {
+            Some(&self.tree)
+        } else {
+            None
+        }
+    }
+}
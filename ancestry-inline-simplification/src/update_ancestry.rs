@@ -1,6 +1,5 @@
-use std::process::Output;
-
 use crate::ancestry_overlapper::AncestryOverlapper;
+use crate::coalescence::CoalescenceSink;
 use crate::node::{Node, NodeData};
 use crate::segments::HalfOpenInterval;
 use crate::segments::Segment;
@@ -14,6 +13,7 @@ use crate::LargeSignedInteger;
 
 #[inline(never)]
 fn intersecting_ancestry(node: &Node) -> Vec<AncestryIntersection> {
+    ancestry_common::phase!("intersecting_ancestry");
     let mut rv = vec![];
     let node_data = node.borrow();
 
@@ -25,7 +25,7 @@ fn intersecting_ancestry(node: &Node) -> Vec<AncestryIntersection> {
                     rv.push(AncestryIntersection::new(
                         std::cmp::max(x.left(), seg.left()),
                         std::cmp::min(x.right(), seg.right()),
-                        x.child.clone(),
+                        x.mapped_node(child),
                     ));
                 }
             }
@@ -73,12 +73,68 @@ fn update_child_segments(
     }
 }
 
+// (left, right, num_lineages)
+type Coalescence = (LargeSignedInteger, LargeSignedInteger, usize);
+
+fn num_lineages_at(coalescences: &[Coalescence], x: LargeSignedInteger) -> usize {
+    let i = coalescences.partition_point(|c| c.1 <= x);
+    match coalescences.get(i) {
+        Some(c) if c.0 <= x => c.2,
+        _ => 0,
+    }
+}
+
+// Reports where the coalescences of a node differ from those
+// reported before, merging adjacent intervals with the same change.
+#[inline(never)]
+fn report_coalescence_changes(
+    sink: &mut dyn CoalescenceSink,
+    birth_time: LargeSignedInteger,
+    previous: &[Coalescence],
+    current: &[Coalescence],
+) {
+    if previous == current {
+        return;
+    }
+    let mut breakpoints = previous
+        .iter()
+        .chain(current.iter())
+        .flat_map(|c| [c.0, c.1])
+        .collect::<Vec<_>>();
+    breakpoints.sort_unstable();
+    breakpoints.dedup();
+
+    let mut pending: Option<(LargeSignedInteger, LargeSignedInteger, usize, usize)> = None;
+    for w in breakpoints.windows(2) {
+        let (left, right) = (w[0], w[1]);
+        let before = num_lineages_at(previous, left);
+        let after = num_lineages_at(current, left);
+        if before == after {
+            continue;
+        }
+        match pending.as_mut() {
+            Some(p) if p.1 == left && p.2 == before && p.3 == after => p.1 = right,
+            _ => {
+                if let Some((l, r, b, a)) = pending {
+                    sink.coalescence(birth_time, l, r, b, a);
+                }
+                pending = Some((left, right, before, after));
+            }
+        }
+    }
+    if let Some((l, r, b, a)) = pending {
+        sink.coalescence(birth_time, l, r, b, a);
+    }
+}
+
 #[inline(never)]
 fn process_overlaps(
     overlapper: &mut AncestryOverlapper,
     output_ancestry: &mut Vec<AncestrySegment>,
     node: &mut Node,
+    coalescences: &mut Option<Vec<Coalescence>>,
 ) {
+    ancestry_common::phase!("process_overlaps");
     let mut borrowed_node = node.borrow_mut();
     for (left, right, overlaps) in overlapper {
         assert!(left < right);
@@ -92,6 +148,13 @@ fn process_overlaps(
             }
         } else {
             debug_assert!(*node == mapped_node);
+            if let Some(coalescences) = coalescences {
+                let num_lineages = borrowed_overlaps.len();
+                match coalescences.last_mut() {
+                    Some(c) if c.1 == left && c.2 == num_lineages => c.1 = right,
+                    _ => coalescences.push((left, right, num_lineages)),
+                }
+            }
             for overlap in borrowed_overlaps.iter() {
                 update_child_segments(&mut borrowed_node, &overlap.mapped_node, left, right);
             }
@@ -99,7 +162,7 @@ fn process_overlaps(
         if !borrowed_node.is_alive() {
             let need_push = match output_ancestry.last_mut() {
                 Some(seg) => {
-                    if seg.right() == left && seg.child == mapped_node {
+                    if seg.right() == left && seg.mapped_node(node) == mapped_node {
                         seg.segment.right = right;
                         false
                     } else {
@@ -109,14 +172,46 @@ fn process_overlaps(
                 None => true,
             };
             if need_push {
-                output_ancestry.push(AncestrySegment::new(left, right, mapped_node));
+                output_ancestry.push(if mapped_node == *node {
+                    AncestrySegment::new_mapping_to_self(left, right)
+                } else {
+                    AncestrySegment::new(left, right, mapped_node)
+                });
             }
         }
     }
 }
 
+// A dead node's mutations follow its ancestry:
+// they stay put where the node is a coalescence,
+// move to the descendant that the ancestry maps to where
+// the node is unary, and are lost where there is no ancestry.
+#[inline(never)]
+fn transfer_mutations(node: &mut Node) {
+    let mut borrowed_node = node.borrow_mut();
+    let mutations = std::mem::take(&mut borrowed_node.mutations);
+    for m in mutations {
+        let i = borrowed_node
+            .ancestry
+            .partition_point(|a| (a.right() as f64) <= m.position);
+        let mapped_node = match borrowed_node.ancestry.get(i) {
+            Some(a) if (a.left() as f64) <= m.position => a.mapped_node(node),
+            _ => continue,
+        };
+        if mapped_node == *node {
+            borrowed_node.mutations.push(m);
+        } else {
+            mapped_node.borrow_mut().mutations.push(m);
+        }
+    }
+}
+
 #[inline(never)]
-pub(crate) fn update_ancestry(node: &mut Node) -> bool {
+pub(crate) fn update_ancestry(
+    node: &mut Node,
+    coalescence_sink: &mut Option<Box<dyn CoalescenceSink>>,
+) -> bool {
+    ancestry_common::phase!("update_ancestry");
     let self_alive = node.is_alive();
 
     let mut overlapper = make_overlapper(node);
@@ -128,14 +223,31 @@ pub(crate) fn update_ancestry(node: &mut Node) -> bool {
 
         for child in borrowed_node.children.keys() {
             let mut mut_borrowed_child = child.borrow_mut();
-            assert!(mut_borrowed_child.parents.contains(node));
-            mut_borrowed_child.parents.remove(node);
+            assert!(mut_borrowed_child.parents.remove(&node.downgrade()));
         }
 
         borrowed_node.children.clear();
     }
 
-    process_overlaps(&mut overlapper, &mut output_ancestry, node);
+    let mut coalescences = coalescence_sink.as_ref().map(|_| Vec::new());
+
+    process_overlaps(
+        &mut overlapper,
+        &mut output_ancestry,
+        node,
+        &mut coalescences,
+    );
+
+    if let (Some(sink), Some(coalescences)) = (coalescence_sink, coalescences) {
+        let mut borrowed_node = node.borrow_mut();
+        let previous = std::mem::replace(&mut borrowed_node.reported_coalescences, coalescences);
+        report_coalescence_changes(
+            sink.as_mut(),
+            borrowed_node.birth_time,
+            &previous,
+            &borrowed_node.reported_coalescences,
+        );
+    }
 
     //if !self_alive {
     //    // Remove trailing input ancestry
@@ -145,11 +257,10 @@ pub(crate) fn update_ancestry(node: &mut Node) -> bool {
     //    }
     //}
 
-    debug_assert!(!node.borrow().parents.contains(node));
+    debug_assert!(!node.borrow().parents.contains(&node.downgrade()));
 
     for child in node.borrow_mut().children.keys() {
-        child.borrow_mut().parents.insert(node.clone());
-        assert!(child.borrow().parents.contains(node));
+        child.borrow_mut().parents.insert(node.downgrade());
     }
 
     // println!("before logic {:?} -> {:?}", output_ancestry, node.borrow().ancestry);
@@ -168,6 +279,10 @@ pub(crate) fn update_ancestry(node: &mut Node) -> bool {
         }
     };
 
+    if !self_alive {
+        transfer_mutations(node);
+    }
+
     //if ancestry_change_detected {
     //    println!("{:?} -> {:?}", output_ancestry, node.borrow().ancestry);
     //}
@@ -22,9 +22,10 @@ pub fn all_reachable_nodes(nodes: &[Node]) -> HashSet<Node> {
         let mut stack = vec![node.clone()];
         while let Some(popped) = stack.pop() {
             reachable.insert(popped.clone());
-            for parent in &popped.borrow().parents {
-                if !reachable.contains(parent) {
-                    stack.push(parent.clone());
+            for parent in popped.borrow().parents.iter() {
+                let parent = parent.upgrade().unwrap();
+                if !reachable.contains(&parent) {
+                    stack.push(parent);
                 }
             }
         }
@@ -33,6 +34,46 @@ pub fn all_reachable_nodes(nodes: &[Node]) -> HashSet<Node> {
     reachable
 }
 
+// Checks the nodes changed by propagating ancestry changes.
+// Once changes have been propagated, a node is reachable iff it
+// is alive or has children, so no traversal is needed.
+#[cfg(debug_assertions)]
+pub(crate) fn validate_nodes(
+    nodes: &[Node],
+    genome_length: crate::LargeSignedInteger,
+) -> Result<(), InlineAncestryError> {
+    let is_reachable = |node: &Node| {
+        let b = node.borrow();
+        b.is_alive() || !b.children.is_empty()
+    };
+    for node in nodes {
+        node.non_overlapping_segments()?;
+        let borrowed_node = node.borrow();
+        if borrowed_node.is_alive() {
+            assert_eq!(borrowed_node.ancestry.len(), 1);
+            assert!(borrowed_node
+                .ancestry
+                .iter()
+                .all(|a| a.left() == 0 && a.right() == genome_length));
+        }
+        if !is_reachable(node) {
+            assert!(borrowed_node.parents.is_empty());
+            continue;
+        }
+        for child in borrowed_node.children.keys() {
+            if !is_reachable(child) {
+                return Err(InlineAncestryError::UnreachableChild);
+            }
+            assert!(child.borrow().parents.contains(&node.downgrade()));
+        }
+        for parent in borrowed_node.parents.iter() {
+            let parent = parent.upgrade().unwrap();
+            assert!(parent.borrow().children.contains_key(node));
+        }
+    }
+    Ok(())
+}
+
 pub fn validate_graph(
     nodes: &[Node],
     genome_length: crate::LargeSignedInteger,
@@ -0,0 +1,111 @@
+use ancestry_inline_simplification::*;
+use neutral_evolution::{evolve, MutationModel, Parameters};
+use proptest::prelude::*;
+use tskit_evolution::{EdgeStrategy, EvolvableTableCollection, SimplificationPolicy};
+
+#[derive(Copy, Clone, Debug)]
+struct SimulationCase {
+    seed: u64,
+    popsize: SignedInteger,
+    genome_length: LargeSignedInteger,
+    death_probability: f64,
+    mean_num_crossovers: f64,
+    nsteps: LargeSignedInteger,
+    simplification_interval: LargeSignedInteger,
+    mean_num_mutations: f64,
+    mutation_model: MutationModel,
+    edge_strategy: EdgeStrategy,
+}
+
+fn simulate(case: &SimulationCase) -> (tskit::TableCollection, tskit::TableCollection) {
+    let mut pop = Population::new(case.popsize, case.genome_length).unwrap();
+    let mut evolveable_tables = EvolvableTableCollection::new(
+        case.genome_length,
+        case.popsize,
+        SimplificationPolicy::Interval(case.simplification_interval),
+        case.edge_strategy,
+    )
+    .unwrap();
+    let p = Parameters::new(
+        case.death_probability,
+        case.mean_num_crossovers,
+        case.nsteps,
+    )
+    .unwrap()
+    .with_mutations(case.mean_num_mutations, case.mutation_model)
+    .unwrap();
+    evolve(case.seed, p, &mut pop).unwrap();
+    evolve(case.seed, p, &mut evolveable_tables).unwrap();
+
+    (
+        tskit::TableCollection::try_from(pop).unwrap(),
+        tskit::TableCollection::from(evolveable_tables),
+    )
+}
+
+fn check_case(case: &SimulationCase) -> Result<(), TestCaseError> {
+    let (pop_tables, tables) = simulate(case);
+    if let Err(e) = tskit_evolution::compare_genealogies_by_sample_order(&pop_tables, &tables) {
+        return Err(TestCaseError::fail(format!("{}", e)));
+    }
+    Ok(())
+}
+
+fn simulation_case() -> impl Strategy<Value = SimulationCase> {
+    (
+        any::<u64>(),
+        2..20_i32,
+        2..200_i64,
+        prop_oneof![Just(1.0), 0.05..1.0_f64],
+        0.0..5.0_f64,
+        1..100_i64,
+        1..10_i64,
+        prop_oneof![Just(0.0), 0.0..5.0_f64],
+        prop_oneof![
+            Just(MutationModel::InfiniteSites),
+            Just(MutationModel::FiniteSites)
+        ],
+        prop_oneof![
+            Just(EdgeStrategy::IncrementalSort),
+            Just(EdgeStrategy::FullSort),
+            Just(EdgeStrategy::Buffer)
+        ],
+    )
+        .prop_map(
+            |(
+                seed,
+                popsize,
+                genome_length,
+                death_probability,
+                mean_num_crossovers,
+                nsteps,
+                simplification_interval,
+                mean_num_mutations,
+                mutation_model,
+                edge_strategy,
+            )| SimulationCase {
+                seed,
+                popsize,
+                genome_length,
+                death_probability,
+                mean_num_crossovers,
+                nsteps,
+                simplification_interval,
+                mean_num_mutations,
+                mutation_model,
+                edge_strategy,
+            },
+        )
+}
+
+// No case has failed so far. When one does, proptest saves its seed
+// in equivalence.proptest-regressions, which is replayed before any
+// new cases on every run, so that file should be committed.
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn test_backends_have_identical_trees(case in simulation_case()) {
+        check_case(&case)?;
+    }
+}
@@ -1,15 +1,23 @@
 use ancestry_inline_simplification::*;
-use neutral_evolution::{evolve, Parameters};
+use neutral_evolution::{
+    evolve, replay, EvolveAncestry, Mutation, MutationModel, Parameters, ScriptedBirth,
+    TransmittedSegment,
+};
 use tskit::prelude::*;
-use tskit_evolution::EvolvableTableCollection;
+use tskit_evolution::{EdgeStrategy, EvolvableTableCollection, SimplificationPolicy};
 
 #[test]
 fn test_simulation_round_trip_nonoverlapping_gens() {
     for seed in [101, 201, 301, 401, 8512389, 12853581239, 95192] {
         // number of haploids, genome length
         let mut pop = Population::new(10, 100).unwrap();
-        let mut evolveable_tables =
-            tskit_evolution::EvolvableTableCollection::new(100, 10, 10).unwrap();
+        let mut evolveable_tables = tskit_evolution::EvolvableTableCollection::new(
+            100,
+            10,
+            SimplificationPolicy::Interval(10),
+            tskit_evolution::EdgeStrategy::IncrementalSort,
+        )
+        .unwrap();
         // death rate, mean no. crossovers, no. steps to sim
         let p = Parameters::new(1.0, 1e-3, 250).unwrap();
         evolve(seed, p, &mut pop).unwrap();
@@ -19,8 +27,8 @@ fn test_simulation_round_trip_nonoverlapping_gens() {
             assert_eq!(i.borrow().birth_time, 250);
             let mut stack = vec![i.clone()];
             while let Some(node) = stack.pop() {
-                for p in &node.borrow().parents {
-                    stack.push(p.clone());
+                for p in node.borrow().parents.iter() {
+                    stack.push(p.upgrade().unwrap());
                 }
             }
         }
@@ -42,6 +50,8 @@ fn test_simulation_round_trip_nonoverlapping_gens() {
                 .unwrap();
             assert_eq!(tseq.num_trees(), pseq.num_trees(), "{}", seed);
         }
+        tskit_evolution::compare_genealogies_by_sample_order(&pop_tables, &tables)
+            .unwrap_or_else(|e| panic!("{}: {}", seed, e));
     }
 }
 
@@ -50,8 +60,13 @@ fn test_simulation_round_trip_overlapping_gens() {
     for seed in [101, 201, 301, 401, 8512389, 12853581239, 95192] {
         for pdeath in [0.25, 0.5, 0.75, 0.9] {
             let mut pop = Population::new(10, 100).unwrap();
-            let mut evolveable_tables =
-                tskit_evolution::EvolvableTableCollection::new(100, 10, 10).unwrap();
+            let mut evolveable_tables = tskit_evolution::EvolvableTableCollection::new(
+                100,
+                10,
+                SimplificationPolicy::Interval(10),
+                tskit_evolution::EdgeStrategy::IncrementalSort,
+            )
+            .unwrap();
             let p = Parameters::new(pdeath, 1e-1, 250).unwrap();
             evolve(seed, p, &mut pop).unwrap();
             evolve(seed, p, &mut evolveable_tables).unwrap();
@@ -104,9 +119,11 @@ fn test_simulation_round_trip_overlapping_gens() {
                     .tree_sequence(tskit::TreeSequenceFlags::BUILD_INDEXES)
                     .unwrap();
                 assert_eq!(ts.num_trees(), pts.num_trees());
-
             }
 
+            tskit_evolution::compare_genealogies_by_sample_order(&pop_tables, &tables)
+                .unwrap_or_else(|e| panic!("{} {}: {}", seed, pdeath, e));
+
             //{
             //    let tcopy = tables.deepcopy().unwrap();
             //    let pcopy = pop_tables.deepcopy().unwrap();
@@ -150,3 +167,545 @@ fn test_simulation_round_trip_overlapping_gens() {
         }
     }
 }
+
+#[test]
+fn test_mutations_before_any_birth() {
+    let mutations = [Mutation { position: 1.0 }];
+    let mut pop = Population::new(10, 100).unwrap();
+    pop.setup(10);
+    assert!(pop.record_mutations(1, 10, &mutations).is_err());
+    let mut tables = EvolvableTableCollection::new(
+        100,
+        10,
+        SimplificationPolicy::Interval(1),
+        EdgeStrategy::IncrementalSort,
+    )
+    .unwrap();
+    tables.setup(10);
+    assert!(tables.record_mutations(1, 10, &mutations).is_err());
+}
+
+#[test]
+fn test_mutations_match_tskit_backend() {
+    for seed in [101, 201, 301, 8512389] {
+        for pdeath in [1.0, 0.5] {
+            for model in [MutationModel::InfiniteSites, MutationModel::FiniteSites] {
+                let mut pop = Population::new(10, 100).unwrap();
+                let mut evolveable_tables = EvolvableTableCollection::new(
+                    100,
+                    10,
+                    SimplificationPolicy::Interval(7),
+                    EdgeStrategy::IncrementalSort,
+                )
+                .unwrap();
+                let p = Parameters::new(pdeath, 1e-1, 100)
+                    .unwrap()
+                    .with_mutations(1.0, model)
+                    .unwrap();
+                evolve(seed, p, &mut pop).unwrap();
+                evolve(seed, p, &mut evolveable_tables).unwrap();
+                let pop_tables = tskit::TableCollection::try_from(pop).unwrap();
+                let tables = tskit::TableCollection::from(evolveable_tables);
+                assert!(u64::from(pop_tables.mutations().num_rows()) > 0);
+                assert_eq!(
+                    pop_tables.mutations().num_rows(),
+                    tables.mutations().num_rows()
+                );
+                tskit_evolution::compare_genealogies_by_sample_order(&pop_tables, &tables)
+                    .unwrap_or_else(|e| panic!("{} {} {:?}: {}", seed, pdeath, model, e));
+            }
+        }
+    }
+}
+
+// Branch-mode AFS from the local trees, as defined by tskit.
+fn tree_sequence_branch_sfs(tables: &tskit::TableCollection, polarised: bool) -> Vec<f64> {
+    let times = tables
+        .nodes_iter()
+        .map(|n| f64::from(n.time))
+        .collect::<Vec<_>>();
+    let samples = tables
+        .nodes_iter()
+        .filter(|n| n.flags.is_sample())
+        .map(|n| n.id)
+        .collect::<Vec<_>>();
+    let n = samples.len();
+    let mut sfs = vec![0.0; n + 1];
+    let ts = tables
+        .deepcopy()
+        .unwrap()
+        .tree_sequence(tskit::TreeSequenceFlags::BUILD_INDEXES)
+        .unwrap();
+    let mut trees = ts.tree_iterator(tskit::TreeFlags::default()).unwrap();
+    while let Some(tree) = trees.next() {
+        let (left, right) = tree.interval();
+        let span = f64::from(right) - f64::from(left);
+        let parents = tree.parent_array();
+        let mut below = vec![0_usize; parents.len()];
+        for s in samples.iter() {
+            let mut u = *s;
+            while !u.is_null() {
+                below[usize::from(u)] += 1;
+                u = parents[usize::from(u)];
+            }
+        }
+        for (u, p) in parents.iter().enumerate() {
+            if !p.is_null() {
+                let k = if !polarised && 2 * below[u] > n {
+                    n - below[u]
+                } else {
+                    below[u]
+                };
+                sfs[k] += span * (times[usize::from(*p)] - times[u]);
+            }
+        }
+    }
+    sfs
+}
+
+#[test]
+fn test_branch_sfs_matches_tree_sequence() {
+    for seed in [101, 201, 8512389] {
+        for pdeath in [1.0, 0.5, 0.1] {
+            let mut pop = Population::new(10, 100).unwrap();
+            let mut evolveable_tables = EvolvableTableCollection::new(
+                100,
+                10,
+                SimplificationPolicy::Interval(7),
+                EdgeStrategy::IncrementalSort,
+            )
+            .unwrap();
+            let p = Parameters::new(pdeath, 1e-1, 100).unwrap();
+            evolve(seed, p, &mut pop).unwrap();
+            evolve(seed, p, &mut evolveable_tables).unwrap();
+            let simplified_tables = tskit::TableCollection::from(evolveable_tables);
+            let polarised = pop.branch_sfs(true, false);
+            let folded = pop.branch_sfs(false, false);
+            let normalised = pop.branch_sfs(true, true);
+            let tables = tskit::TableCollection::try_from(pop).unwrap();
+            for (mine, theirs) in [
+                (polarised.clone(), tree_sequence_branch_sfs(&tables, true)),
+                (folded, tree_sequence_branch_sfs(&tables, false)),
+                (
+                    polarised.clone(),
+                    tree_sequence_branch_sfs(&simplified_tables, true),
+                ),
+            ] {
+                assert_eq!(mine.len(), 11);
+                for (a, b) in mine.iter().zip(theirs.iter()) {
+                    assert!(
+                        (a - b).abs() < 1e-8,
+                        "{} {}: {:?} {:?}",
+                        seed,
+                        pdeath,
+                        mine,
+                        theirs
+                    );
+                }
+            }
+            for (a, b) in normalised.iter().zip(polarised.iter()) {
+                assert!((a - b / 100.0).abs() < 1e-8);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_local_trees_match_tree_sequence() {
+    for seed in [101, 201, 8512389] {
+        for pdeath in [1.0, 0.5, 0.1] {
+            let mut pop = Population::new(10, 100).unwrap();
+            let p = Parameters::new(pdeath, 1e-1, 100).unwrap();
+            evolve(seed, p, &mut pop).unwrap();
+            let mut local_trees = pop.trees();
+            let tables = tskit::TableCollection::try_from(pop).unwrap();
+            let times = tables
+                .nodes_iter()
+                .map(|n| f64::from(n.time))
+                .collect::<Vec<_>>();
+            let ts = tables
+                .tree_sequence(tskit::TreeSequenceFlags::BUILD_INDEXES)
+                .unwrap();
+            let mut num_trees = 0;
+            let mut trees = ts.tree_iterator(tskit::TreeFlags::default()).unwrap();
+            while let Some(tree) = trees.next() {
+                num_trees += 1;
+                let local_tree = local_trees.next().unwrap();
+                let (left, right) = tree.interval();
+                assert_eq!(
+                    local_tree.interval(),
+                    (
+                        f64::from(left) as LargeSignedInteger,
+                        f64::from(right) as LargeSignedInteger
+                    )
+                );
+                // Alive nodes come first in both, in population order,
+                // so the paths from each sample to its root can be
+                // compared via the branch lengths along them.
+                let parents = tree.parent_array();
+                for s in local_tree.samples() {
+                    let mut path = vec![];
+                    let mut u = s;
+                    while let Some(p) = local_tree.parent(u) {
+                        path.push((local_tree.birth_time(u) - local_tree.birth_time(p)) as f64);
+                        u = p;
+                    }
+                    let mut tskit_path = vec![];
+                    let mut u = tskit::NodeId::from(s as i32);
+                    while !parents[usize::from(u)].is_null() {
+                        let p = parents[usize::from(u)];
+                        tskit_path.push(times[usize::from(p)] - times[usize::from(u)]);
+                        u = p;
+                    }
+                    assert_eq!(path, tskit_path, "{} {}", seed, pdeath);
+                }
+            }
+            assert!(local_trees.next().is_none());
+            assert_eq!(u64::from(ts.num_trees()), num_trees);
+        }
+    }
+}
+
+// (left, right, ancestor time) for each maximal run of trees
+// in which `samples` have the same MRCA.
+fn tree_sequence_mrcas(
+    tables: &tskit::TableCollection,
+    samples: &[usize],
+) -> Vec<(LargeSignedInteger, LargeSignedInteger, f64)> {
+    let times = tables
+        .nodes_iter()
+        .map(|n| f64::from(n.time))
+        .collect::<Vec<_>>();
+    let ts = tables
+        .deepcopy()
+        .unwrap()
+        .tree_sequence(tskit::TreeSequenceFlags::BUILD_INDEXES)
+        .unwrap();
+    let mut rv: Vec<(LargeSignedInteger, LargeSignedInteger, tskit::NodeId)> = vec![];
+    let mut trees = ts.tree_iterator(tskit::TreeFlags::default()).unwrap();
+    while let Some(tree) = trees.next() {
+        let parents = tree.parent_array();
+        let ancestors = |s: usize| {
+            let mut rv = vec![tskit::NodeId::from(s as i32)];
+            while !parents[usize::from(*rv.last().unwrap())].is_null() {
+                rv.push(parents[usize::from(*rv.last().unwrap())]);
+            }
+            rv
+        };
+        let mut common = ancestors(samples[0]);
+        for &s in &samples[1..] {
+            let a = ancestors(s);
+            common.retain(|u| a.contains(u));
+        }
+        let mrca = match common.first() {
+            Some(mrca) => *mrca,
+            None => continue,
+        };
+        let (left, right) = tree.interval();
+        let (left, right) = (
+            f64::from(left) as LargeSignedInteger,
+            f64::from(right) as LargeSignedInteger,
+        );
+        match rv.last_mut() {
+            Some(last) if last.1 == left && last.2 == mrca => last.1 = right,
+            _ => rv.push((left, right, mrca)),
+        }
+    }
+    rv.into_iter()
+        .map(|(l, r, u)| (l, r, times[usize::from(u)]))
+        .collect()
+}
+
+#[test]
+fn test_ibd_segments_match_tree_sequence() {
+    for seed in [101, 201, 8512389] {
+        for pdeath in [1.0, 0.5, 0.1] {
+            let mut pop = Population::new(10, 100).unwrap();
+            let p = Parameters::new(pdeath, 1e-1, 100).unwrap();
+            evolve(seed, p, &mut pop).unwrap();
+            let sample_sets = [vec![0, 1], vec![2, 7], vec![3, 3], vec![0, 4, 9]];
+            let mut results = vec![];
+            for samples in sample_sets.iter() {
+                let segments = pop.ibd_segments(samples, 0).unwrap();
+                let filtered = pop.ibd_segments(samples, 10).unwrap();
+                assert_eq!(
+                    filtered
+                        .iter()
+                        .map(|s| (s.left, s.right))
+                        .collect::<Vec<_>>(),
+                    segments
+                        .iter()
+                        .filter(|s| s.right - s.left >= 10)
+                        .map(|s| (s.left, s.right))
+                        .collect::<Vec<_>>()
+                );
+                let birth_time = pop.nodes[samples[0]].borrow().birth_time;
+                results.push((birth_time, segments));
+            }
+            assert!(matches!(
+                pop.ibd_segments(&[0, 10], 0),
+                Err(InlineAncestryError::InvalidAliveNode { index: 10 })
+            ));
+            let tables = tskit::TableCollection::try_from(pop).unwrap();
+            for (samples, (birth_time, segments)) in sample_sets.iter().zip(results) {
+                // Alive nodes come first in the exported tables.
+                let sample_time = f64::from(
+                    tables
+                        .nodes()
+                        .time(tskit::NodeId::from(samples[0] as i32))
+                        .unwrap(),
+                );
+                let expected = tree_sequence_mrcas(&tables, samples);
+                let segments = segments
+                    .iter()
+                    .map(|s| {
+                        (
+                            s.left,
+                            s.right,
+                            sample_time + (birth_time - s.ancestor_birth_time) as f64,
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                assert_eq!(segments, expected, "{} {} {:?}", seed, pdeath, samples);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_coalescence_sink() {
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use std::rc::Rc;
+
+    // Both nodes die. Node 0 is the only parent of the first
+    // birth and the left parent of the second, so its two
+    // descendant lineages merge on [0, 5).
+    let generations = vec![vec![
+        ScriptedBirth::new(0, vec![TransmittedSegment::new(0, 10, 0)]),
+        ScriptedBirth::new(
+            1,
+            vec![
+                TransmittedSegment::new(0, 5, 0),
+                TransmittedSegment::new(5, 10, 1),
+            ],
+        ),
+    ]];
+    let events = Rc::new(RefCell::new(vec![]));
+    let mut pop = Population::new(2, 10).unwrap();
+    {
+        let events = events.clone();
+        pop.set_coalescence_sink(Box::new(move |birth_time, left, right, before, after| {
+            events
+                .borrow_mut()
+                .push((birth_time, left, right, before, after))
+        }));
+    }
+    replay(&generations, &mut pop).unwrap();
+    assert_eq!(*events.borrow(), vec![(0, 0, 5, 0, 2)]);
+
+    assert!(pop.take_coalescence_sink().is_some());
+    assert!(pop.take_coalescence_sink().is_none());
+
+    // Applying every change gives the coalescences left in the graph,
+    // weighted by span, for each birth time.
+    for pdeath in [1.0, 0.5] {
+        let events = Rc::new(RefCell::new(vec![]));
+        let mut pop = Population::new(10, 100).unwrap();
+        {
+            let events = events.clone();
+            pop.set_coalescence_sink(Box::new(move |birth_time, left, right, before, after| {
+                events
+                    .borrow_mut()
+                    .push((birth_time, left, right, before, after))
+            }));
+        }
+        let p = Parameters::new(pdeath, 1e-1, 10).unwrap();
+        evolve(101, p, &mut pop).unwrap();
+        assert!(!events.borrow().is_empty());
+
+        let mut from_events = HashMap::<i64, i64>::new();
+        for &(birth_time, left, right, before, after) in events.borrow().iter() {
+            assert!((0..100).contains(&birth_time));
+            assert!(0 <= left && left < right && right <= 100);
+            assert_ne!(before, after);
+            assert!(before != 1 && after != 1);
+            *from_events.entry(birth_time).or_default() +=
+                (after as i64 - before as i64) * (right - left);
+        }
+        from_events.retain(|_, v| *v != 0);
+
+        let mut from_graph = HashMap::<i64, i64>::new();
+        for node in pop.all_reachable_nodes() {
+            let node = node.borrow();
+            let mut breakpoints = vec![];
+            for segs in node.children.values() {
+                for s in segs {
+                    breakpoints.push((s.left, 1));
+                    breakpoints.push((s.right, -1));
+                }
+            }
+            breakpoints.sort_unstable();
+            let mut depth = 0_i64;
+            let mut last = 0;
+            for (x, d) in breakpoints {
+                if depth > 1 {
+                    *from_graph.entry(node.birth_time).or_default() += depth * (x - last);
+                }
+                depth += d;
+                last = x;
+            }
+        }
+        assert_eq!(from_events, from_graph);
+    }
+}
+
+#[test]
+fn test_graph_stats_match_traversal() {
+    for seed in [101, 201] {
+        for pdeath in [1.0, 0.5, 0.1] {
+            let mut pop = Population::new(10, 100).unwrap();
+            let p = Parameters::new(pdeath, 1e-1, 50).unwrap();
+            evolve(seed, p, &mut pop).unwrap();
+            let reachable = pop.all_reachable_nodes();
+            let stats = pop.graph_stats();
+            assert_eq!(stats.num_reachable_nodes, reachable.len());
+            assert_eq!(pop.num_still_reachable(), reachable.len());
+            assert_eq!(
+                stats.num_ancestry_segments,
+                reachable
+                    .iter()
+                    .map(|n| n.borrow().ancestry.len())
+                    .sum::<usize>()
+            );
+            assert_eq!(
+                stats.num_child_segments,
+                reachable
+                    .iter()
+                    .map(|n| n.borrow().children.values().map(|s| s.len()).sum::<usize>())
+                    .sum::<usize>()
+            );
+            assert_eq!(
+                stats.num_roots,
+                reachable
+                    .iter()
+                    .filter(|n| n.borrow().parents.is_empty())
+                    .count()
+            );
+        }
+    }
+}
+
+#[test]
+fn test_unreachable_nodes_are_freed() {
+    // Tests run on their own threads, so only
+    // this test's nodes are counted.
+    assert_eq!(num_live_nodes(), 0);
+    for pdeath in [1.0, 0.5, 0.1] {
+        let mut pop = Population::new(20, 1000).unwrap();
+        let p = Parameters::new(pdeath, 1e-2, 2000).unwrap();
+        evolve(101, p, &mut pop).unwrap();
+        assert_eq!(num_live_nodes(), pop.num_still_reachable());
+        // Many more nodes were born than remain.
+        assert!(pop.num_still_reachable() < 1000);
+        drop(pop);
+        assert_eq!(num_live_nodes(), 0);
+    }
+}
+
+#[test]
+fn test_nodes_outlive_population() {
+    assert_eq!(num_live_nodes(), 0);
+    let mut pop = Population::new(20, 1000).unwrap();
+    let p = Parameters::new(0.5, 1.0, 100).unwrap();
+    evolve(101, p, &mut pop).unwrap();
+    let alive = pop.nodes[0].clone();
+    let parent = alive
+        .borrow()
+        .parents
+        .iter()
+        .next()
+        .unwrap()
+        .upgrade()
+        .unwrap();
+    let num_children = parent.borrow().children.len();
+    let ancestry = parent.borrow().ancestry.clone();
+    assert!(num_children > 0);
+    drop(pop);
+
+    assert_eq!(alive.borrow().ancestry.len(), 1);
+    assert!(alive.borrow().ancestry[0].mapped_node(&alive) == alive);
+    assert_eq!(parent.borrow().children.len(), num_children);
+    assert!(parent.borrow().children.contains_key(&alive));
+    assert_eq!(parent.borrow().ancestry, ancestry);
+    assert!(num_live_nodes() > 0);
+    drop(ancestry);
+    drop(alive);
+    drop(parent);
+    assert_eq!(num_live_nodes(), 0);
+}
+
+#[test]
+fn test_individuals_match_tskit_backend() {
+    use tskit::provenance::Provenance;
+
+    for pdeath in [1.0, 0.5] {
+        let p = Parameters::new(pdeath, 1e-1, 100).unwrap();
+        let mut pop = Population::new(10, 100).unwrap();
+        let mut evolveable_tables = EvolvableTableCollection::new(
+            100,
+            10,
+            SimplificationPolicy::Interval(7),
+            EdgeStrategy::IncrementalSort,
+        )
+        .unwrap();
+        evolve(101, p, &mut pop).unwrap();
+        evolve(101, p, &mut evolveable_tables).unwrap();
+        let pop_tables = tskit::TableCollection::try_from(pop).unwrap();
+        let tables = tskit::TableCollection::from(evolveable_tables);
+
+        let mut num_parents = vec![];
+        let mut individual_parents = vec![];
+        for (tables, name) in [
+            (&pop_tables, "ancestry-inline-simplification"),
+            (&tables, "tskit-evolution"),
+        ] {
+            assert_eq!(u64::from(tables.populations().num_rows()), 1);
+            assert_eq!(u64::from(tables.provenances().num_rows()), 1);
+            let record = tables.provenances().record(0).unwrap();
+            assert!(record.contains(&format!(r#""name":"{}""#, name)));
+            assert!(record.contains(r#""seed":101,"#));
+
+            let individuals = tables.individuals_iter().collect::<Vec<_>>();
+            for i in individuals.iter() {
+                assert!(i.parents.iter().flatten().all(|p| p.is_null() || *p < i.id));
+            }
+            let nodes = tables.nodes_iter().collect::<Vec<_>>();
+            assert!(nodes
+                .iter()
+                .all(|n| n.population == 0 && !n.individual.is_null()));
+            // Each node has its own individual, and no others remain.
+            assert_eq!(individuals.len(), nodes.len());
+            individual_parents.push(
+                individuals
+                    .iter()
+                    .map(|i| i.parents.clone().unwrap_or_default())
+                    .collect::<Vec<_>>(),
+            );
+            num_parents.push(
+                nodes
+                    .iter()
+                    .filter(|n| n.flags.contains(tskit::NodeFlags::IS_SAMPLE))
+                    .map(|n| {
+                        individuals[usize::from(n.individual)]
+                            .parents
+                            .as_ref()
+                            .map_or(0, |p| p.len())
+                    })
+                    .collect::<Vec<_>>(),
+            );
+        }
+        assert_eq!(num_parents[0], num_parents[1]);
+        assert_eq!(individual_parents[0], individual_parents[1]);
+    }
+}
@@ -0,0 +1,19 @@
+use crate::LargeSignedInteger;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum TestSupportError {
+    #[error("invalid genome length: {l:?}")]
+    InvalidGenomeLength { l: LargeSignedInteger },
+    #[error("invalid node: {node:?}")]
+    InvalidNode { node: usize },
+    #[error("duplicate sample: {node:?}")]
+    DuplicateSample { node: usize },
+    #[error("invalid edge: [{left:?}, {right:?})")]
+    InvalidEdge {
+        left: LargeSignedInteger,
+        right: LargeSignedInteger,
+    },
+    #[error("parent index must be < child index, got {parent:?}, {child:?}")]
+    InvalidNodeOrder { parent: usize, child: usize },
+}
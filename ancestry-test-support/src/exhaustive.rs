@@ -0,0 +1,181 @@
+//! Enumerate every pedigree of a given (small) size.
+//!
+//! Bugs in ancestry updating tend to depend on the exact
+//! pattern of deaths and breakpoints, which random simulations
+//! only find by luck.  For tiny populations and genomes,
+//! we can simply try all of them.
+
+use crate::LargeSignedInteger;
+use crate::TestSupportError;
+use neutral_evolution::{ScriptedBirth, TransmittedSegment};
+
+/// All distinct ways for a child to inherit
+/// `[0, genome_length)` from the population.
+fn all_transmissions(
+    popsize: usize,
+    genome_length: LargeSignedInteger,
+) -> Vec<Vec<TransmittedSegment>> {
+    let num_positions = (genome_length - 1) as usize;
+    let mut rv: Vec<Vec<TransmittedSegment>> = vec![];
+    for p1 in 0..popsize {
+        for p2 in 0..popsize {
+            for mask in 0..(1_usize << num_positions) {
+                if p1 == p2 && mask != 0 {
+                    continue;
+                }
+                let mut transmissions = vec![];
+                let mut left = 0;
+                let (mut current, mut other) = (p1, p2);
+                for i in 0..num_positions {
+                    if mask & (1 << i) != 0 {
+                        let right = (i + 1) as LargeSignedInteger;
+                        transmissions.push(TransmittedSegment::new(left, right, current));
+                        left = right;
+                        std::mem::swap(&mut current, &mut other);
+                    }
+                }
+                transmissions.push(TransmittedSegment::new(left, genome_length, current));
+                if !rv.contains(&transmissions) {
+                    rv.push(transmissions);
+                }
+            }
+        }
+    }
+    rv
+}
+
+/// All distinct sets of births for one time step.
+fn all_generations(
+    popsize: usize,
+    transmissions: &[Vec<TransmittedSegment>],
+) -> Vec<Vec<ScriptedBirth>> {
+    let mut rv = vec![vec![]];
+    for individual in 0..popsize {
+        let mut next = vec![];
+        for births in rv {
+            for t in transmissions {
+                let mut b = births.clone();
+                b.push(ScriptedBirth::new(individual, t.clone()));
+                next.push(b);
+            }
+            // This individual survives
+            next.push(births);
+        }
+        rv = next;
+    }
+    rv
+}
+
+/// Iterates over every pedigree with `popsize` individuals,
+/// a genome of length `genome_length`, and `nsteps` time steps.
+///
+/// Each item is suitable for [neutral_evolution::replay].
+/// Breakpoints are at integer positions, and a child
+/// inherits each position from exactly one parent.
+///
+/// The number of pedigrees grows very quickly:
+/// each step has `(1 + T)^popsize` possibilities, where
+/// `T` is the number of distinct transmissions.
+pub struct ExhaustivePedigrees {
+    generations: Vec<Vec<ScriptedBirth>>,
+    // Index into generations for each time step,
+    // or None once we are done.
+    state: Option<Vec<usize>>,
+}
+
+impl ExhaustivePedigrees {
+    pub fn new(
+        popsize: usize,
+        genome_length: LargeSignedInteger,
+        nsteps: usize,
+    ) -> Result<Self, TestSupportError> {
+        if genome_length < 1 {
+            return Err(TestSupportError::InvalidGenomeLength { l: genome_length });
+        }
+        let transmissions = all_transmissions(popsize, genome_length);
+        let generations = all_generations(popsize, &transmissions);
+        let state = if popsize > 0 && nsteps > 0 {
+            Some(vec![0; nsteps])
+        } else {
+            None
+        };
+        Ok(Self { generations, state })
+    }
+
+    /// The total number of pedigrees.
+    pub fn num_pedigrees(&self) -> usize {
+        match &self.state {
+            Some(s) => self.generations.len().pow(s.len() as u32),
+            None => 0,
+        }
+    }
+}
+
+impl Iterator for ExhaustivePedigrees {
+    type Item = Vec<Vec<ScriptedBirth>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let state = self.state.as_mut()?;
+        let rv = state
+            .iter()
+            .map(|&i| self.generations[i].clone())
+            .collect::<Vec<_>>();
+
+        // Advance like an odometer, with the last step changing fastest.
+        let mut done = true;
+        for i in state.iter_mut().rev() {
+            *i += 1;
+            if *i < self.generations.len() {
+                done = false;
+                break;
+            }
+            *i = 0;
+        }
+        if done {
+            self.state = None;
+        }
+        Some(rv)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_all_transmissions() {
+        assert_eq!(all_transmissions(2, 1).len(), 2);
+        // Two clonal, plus two ordered parent pairs
+        // for the single breakpoint.
+        assert_eq!(all_transmissions(2, 2).len(), 4);
+        assert_eq!(all_transmissions(3, 3).len(), 3 + 6 * 3);
+        for t in all_transmissions(3, 4) {
+            assert_eq!(t.first().unwrap().left, 0);
+            assert_eq!(t.last().unwrap().right, 4);
+            assert!(t.windows(2).all(|w| w[0].right == w[1].left));
+            assert!(t.windows(2).all(|w| w[0].parent != w[1].parent));
+        }
+    }
+
+    #[test]
+    fn test_num_pedigrees() {
+        let pedigrees = ExhaustivePedigrees::new(2, 2, 2).unwrap();
+        assert_eq!(pedigrees.num_pedigrees(), 25 * 25);
+        assert_eq!(pedigrees.count(), 25 * 25);
+    }
+
+    #[test]
+    fn test_pedigrees_are_distinct() {
+        let pedigrees = ExhaustivePedigrees::new(2, 2, 2)
+            .unwrap()
+            .collect::<Vec<_>>();
+        for (i, p) in pedigrees.iter().enumerate() {
+            assert!(!pedigrees.iter().skip(i + 1).any(|q| q == p));
+        }
+    }
+
+    #[test]
+    fn test_bad_genome_length() {
+        assert!(ExhaustivePedigrees::new(2, 0, 2).is_err());
+    }
+}
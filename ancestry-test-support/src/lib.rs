@@ -0,0 +1,12 @@
+pub use ancestry_common::LargeSignedInteger;
+
+mod error;
+mod exhaustive;
+mod pedigree;
+
+pub mod reference_simplifier;
+
+pub use error::TestSupportError;
+pub use exhaustive::ExhaustivePedigrees;
+pub use pedigree::RecordedPedigree;
+pub use reference_simplifier::{simplify, Edge, Node, Segment, SimplifiedTables};
@@ -0,0 +1,132 @@
+use crate::reference_simplifier::{Edge, Node, SimplifiedTables};
+use crate::LargeSignedInteger;
+use crate::TestSupportError;
+use neutral_evolution::EvolveAncestry;
+
+/// Records the entire pedigree of a simulation,
+/// never simplifying, so that the result can be handed
+/// to [simplify](crate::simplify).
+///
+/// Node times follow the same convention as
+/// `tskit_evolution::EvolvableTableCollection`.
+pub struct RecordedPedigree {
+    genome_length: LargeSignedInteger,
+    popsize: usize,
+    nodes: Vec<Node>,
+    edges: Vec<Edge>,
+    alive_nodes: Vec<usize>,
+    replacements: Vec<usize>,
+    births: Vec<usize>,
+}
+
+impl RecordedPedigree {
+    pub fn new(
+        popsize: usize,
+        genome_length: LargeSignedInteger,
+    ) -> Result<Self, TestSupportError> {
+        if genome_length > 0 {
+            Ok(Self {
+                genome_length,
+                popsize,
+                nodes: vec![],
+                edges: vec![],
+                alive_nodes: vec![],
+                replacements: vec![],
+                births: vec![],
+            })
+        } else {
+            Err(TestSupportError::InvalidGenomeLength { l: genome_length })
+        }
+    }
+
+    pub fn nodes(&self) -> &[Node] {
+        &self.nodes
+    }
+
+    pub fn edges(&self) -> &[Edge] {
+        &self.edges
+    }
+
+    pub fn alive_nodes(&self) -> &[usize] {
+        &self.alive_nodes
+    }
+
+    /// Simplify the pedigree with respect to the alive nodes.
+    pub fn simplified(&self) -> Result<SimplifiedTables, TestSupportError> {
+        crate::simplify(
+            &self.alive_nodes,
+            &self.nodes,
+            &self.edges,
+            self.genome_length,
+        )
+    }
+}
+
+impl EvolveAncestry for RecordedPedigree {
+    fn genome_length(&self) -> LargeSignedInteger {
+        self.genome_length
+    }
+
+    fn setup(&mut self, final_time: LargeSignedInteger) {
+        for _ in 0..self.popsize {
+            self.nodes.push(Node::new(final_time));
+            self.alive_nodes.push(self.nodes.len() - 1);
+        }
+    }
+
+    fn generate_deaths(&mut self, death: &mut neutral_evolution::Death) -> usize {
+        self.replacements.clear();
+        for i in 0..self.alive_nodes.len() {
+            if death.dies() {
+                self.replacements.push(i);
+            }
+        }
+        self.replacements.len()
+    }
+
+    fn current_population_size(&self) -> usize {
+        self.popsize
+    }
+
+    fn record_birth(
+        &mut self,
+        birth_time: LargeSignedInteger,
+        final_time: LargeSignedInteger,
+        breakpoints: &[neutral_evolution::TransmittedSegment],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.nodes.push(Node::new(final_time - birth_time));
+        let child = self.nodes.len() - 1;
+        for b in breakpoints {
+            self.edges.push(Edge::new(
+                b.left,
+                b.right,
+                self.alive_nodes[b.parent],
+                child,
+            ));
+        }
+        self.births.push(child);
+        Ok(())
+    }
+
+    fn simplify(
+        &mut self,
+        _current_time_point: LargeSignedInteger,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        assert_eq!(self.replacements.len(), self.births.len());
+        for (r, b) in self.replacements.iter().zip(self.births.iter()) {
+            self.alive_nodes[*r] = *b;
+        }
+        self.births.clear();
+        Ok(())
+    }
+
+    fn finish(
+        &mut self,
+        _current_time_point: LargeSignedInteger,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        for i in self.alive_nodes.iter() {
+            self.nodes[*i].flags |= crate::reference_simplifier::NODE_IS_SAMPLE;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,354 @@
+//! A port of `simaterial.py`: the naive simplification
+//! algorithm that we use as an oracle for both backends.
+//!
+//! Nothing here is fast.  The point is to be simple enough
+//! to trust.
+
+use crate::LargeSignedInteger;
+use crate::TestSupportError;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+pub const NODE_IS_SAMPLE: u32 = 1;
+
+/// A segment of ancestry mapping to an output node.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub struct Segment {
+    pub left: LargeSignedInteger,
+    pub right: LargeSignedInteger,
+    pub node: usize,
+}
+
+/// Times follow the tskit convention: they increase
+/// into the past.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Node {
+    pub time: LargeSignedInteger,
+    pub flags: u32,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Edge {
+    pub left: LargeSignedInteger,
+    pub right: LargeSignedInteger,
+    pub parent: usize,
+    pub child: usize,
+}
+
+impl Segment {
+    pub fn new(left: LargeSignedInteger, right: LargeSignedInteger, node: usize) -> Self {
+        assert!(left < right, "{} {}", left, right);
+        Self { left, right, node }
+    }
+}
+
+impl Node {
+    pub fn new(time: LargeSignedInteger) -> Self {
+        Self { time, flags: 0 }
+    }
+
+    pub fn new_sample(time: LargeSignedInteger) -> Self {
+        Self {
+            time,
+            flags: NODE_IS_SAMPLE,
+        }
+    }
+
+    pub fn is_sample(&self) -> bool {
+        self.flags & NODE_IS_SAMPLE != 0
+    }
+}
+
+impl Edge {
+    pub fn new(
+        left: LargeSignedInteger,
+        right: LargeSignedInteger,
+        parent: usize,
+        child: usize,
+    ) -> Self {
+        Self {
+            left,
+            right,
+            parent,
+            child,
+        }
+    }
+}
+
+pub struct SimplifiedTables {
+    pub nodes: Vec<Node>,
+    /// Squashed and sorted in the order required by tskit.
+    pub edges: Vec<Edge>,
+    /// The ancestry of each *input* node.
+    pub ancestry: Vec<Vec<Segment>>,
+    /// Input node to output node.
+    pub idmap: Vec<Option<usize>>,
+}
+
+fn validate_input(
+    samples: &[usize],
+    nodes: &[Node],
+    edges: &[Edge],
+    genome_length: LargeSignedInteger,
+) -> Result<(), TestSupportError> {
+    if genome_length < 1 {
+        return Err(TestSupportError::InvalidGenomeLength { l: genome_length });
+    }
+    let mut is_sample = vec![false; nodes.len()];
+    for &s in samples {
+        if s >= nodes.len() {
+            return Err(TestSupportError::InvalidNode { node: s });
+        }
+        if is_sample[s] {
+            return Err(TestSupportError::DuplicateSample { node: s });
+        }
+        is_sample[s] = true;
+    }
+    for e in edges {
+        if e.left < 0 || e.right > genome_length || e.left >= e.right {
+            return Err(TestSupportError::InvalidEdge {
+                left: e.left,
+                right: e.right,
+            });
+        }
+        if e.parent >= nodes.len() {
+            return Err(TestSupportError::InvalidNode { node: e.parent });
+        }
+        if e.child >= nodes.len() {
+            return Err(TestSupportError::InvalidNode { node: e.child });
+        }
+        // We visit nodes from last to first, so all children
+        // must be processed before their parents.
+        if e.parent >= e.child {
+            return Err(TestSupportError::InvalidNodeOrder {
+                parent: e.parent,
+                child: e.child,
+            });
+        }
+    }
+    Ok(())
+}
+
+fn squash_and_sort_edges(nodes: &[Node], edges: &mut Vec<Edge>) {
+    edges.sort_by_key(|e| (e.parent, e.child, e.left));
+    let mut squashed: Vec<Edge> = vec![];
+    for e in edges.drain(..) {
+        match squashed.last_mut() {
+            Some(last)
+                if last.parent == e.parent && last.child == e.child && last.right == e.left =>
+            {
+                last.right = e.right
+            }
+            _ => squashed.push(e),
+        }
+    }
+    squashed.sort_by_key(|e| (nodes[e.parent].time, e.parent, e.child, e.left));
+    *edges = squashed;
+}
+
+/// Simplify the nodes and edges with respect to `samples`.
+///
+/// Unlike `simaterial.py`, samples may also be ancestors
+/// of other samples, as happens with overlapping generations.
+///
+/// # Errors
+///
+/// The input must be ordered so that every edge's parent
+/// has a smaller index than its child.
+pub fn simplify(
+    samples: &[usize],
+    nodes: &[Node],
+    edges: &[Edge],
+    genome_length: LargeSignedInteger,
+) -> Result<SimplifiedTables, TestSupportError> {
+    validate_input(samples, nodes, edges, genome_length)?;
+
+    let mut ancestry = vec![vec![]; nodes.len()];
+    let mut idmap = vec![None; nodes.len()];
+    let mut output_nodes = vec![];
+    let mut output_edges = vec![];
+
+    for (i, &u) in samples.iter().enumerate() {
+        output_nodes.push(Node::new_sample(nodes[u].time));
+        ancestry[u].push(Segment::new(0, genome_length, i));
+        idmap[u] = Some(i);
+    }
+
+    // simaterial.py filters the entire edge list for each parent.
+    let mut edges_by_parent = vec![vec![]; nodes.len()];
+    for e in edges {
+        edges_by_parent[e.parent].push(*e);
+    }
+
+    let mut queue = BinaryHeap::new();
+    let mut overlaps = vec![];
+
+    for u in (0..nodes.len()).rev() {
+        assert!(queue.is_empty());
+        for e in &edges_by_parent[u] {
+            for x in &ancestry[e.child] {
+                if x.right > e.left && e.right > x.left {
+                    queue.push(Reverse(Segment::new(
+                        std::cmp::max(x.left, e.left),
+                        std::cmp::min(x.right, e.right),
+                        x.node,
+                    )));
+                }
+            }
+        }
+
+        let is_sample = idmap[u].is_some();
+        let mut output_node = idmap[u];
+
+        while let Some(&Reverse(first)) = queue.peek() {
+            let left = first.left;
+            let mut right = genome_length;
+            overlaps.clear();
+            while let Some(&Reverse(x)) = queue.peek() {
+                if x.left != left {
+                    break;
+                }
+                let _ = queue.pop();
+                right = std::cmp::min(right, x.right);
+                overlaps.push(x);
+            }
+            if let Some(Reverse(next)) = queue.peek() {
+                right = std::cmp::min(right, next.left);
+            }
+            assert!(!overlaps.is_empty());
+            assert!(left < right);
+
+            if overlaps.len() == 1 && !is_sample {
+                // Unary: the ancestry passes through u unchanged.
+                let mut x = overlaps[0];
+                let mut alpha = x;
+                if let Some(Reverse(next)) = queue.peek() {
+                    if next.left < x.right {
+                        alpha.right = next.left;
+                        x.left = next.left;
+                        queue.push(Reverse(x));
+                    }
+                }
+                ancestry[u].push(alpha);
+            } else {
+                let parent = match output_node {
+                    Some(p) => p,
+                    None => {
+                        output_nodes.push(Node::new(nodes[u].time));
+                        let p = output_nodes.len() - 1;
+                        output_node = Some(p);
+                        idmap[u] = Some(p);
+                        p
+                    }
+                };
+                for x in overlaps.iter() {
+                    output_edges.push(Edge::new(left, right, parent, x.node));
+                    if x.right > right {
+                        queue.push(Reverse(Segment::new(right, x.right, x.node)));
+                    }
+                }
+                if !is_sample {
+                    ancestry[u].push(Segment::new(left, right, parent));
+                }
+            }
+        }
+    }
+
+    squash_and_sort_edges(&output_nodes, &mut output_edges);
+
+    Ok(SimplifiedTables {
+        nodes: output_nodes,
+        edges: output_edges,
+        ancestry,
+        idmap,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The example from simaterial.py
+    fn simaterial_example() -> (Vec<Node>, Vec<Edge>) {
+        let nodes = vec![
+            Node::new(2),
+            Node::new(2),
+            Node::new(1),
+            Node::new(1),
+            Node::new(0),
+            Node::new(0),
+        ];
+        let (x, y, l) = (50, 60, 100);
+        let edges = vec![
+            Edge::new(0, x, 0, 2),
+            Edge::new(x, l, 1, 2),
+            Edge::new(0, l, 1, 3),
+            Edge::new(0, y, 2, 5),
+            Edge::new(0, l, 3, 4),
+            Edge::new(y, l, 3, 5),
+        ];
+        (nodes, edges)
+    }
+
+    #[test]
+    fn test_simaterial_example() {
+        let (nodes, edges) = simaterial_example();
+        let simplified = simplify(&[4, 5], &nodes, &edges, 100).unwrap();
+
+        assert_eq!(
+            simplified.idmap,
+            vec![None, Some(3), None, Some(2), Some(0), Some(1)]
+        );
+        assert_eq!(simplified.nodes.len(), 4);
+        assert!(simplified.nodes[0].is_sample());
+        assert!(simplified.nodes[1].is_sample());
+        assert!(!simplified.nodes[2].is_sample());
+        assert_eq!(simplified.nodes[2].time, 1);
+        assert_eq!(simplified.nodes[3].time, 2);
+        assert_eq!(
+            simplified.edges,
+            vec![
+                Edge::new(60, 100, 2, 0),
+                Edge::new(60, 100, 2, 1),
+                Edge::new(50, 60, 3, 0),
+                Edge::new(50, 60, 3, 1),
+            ]
+        );
+        assert_eq!(
+            simplified.ancestry[3],
+            vec![Segment::new(0, 60, 0), Segment::new(60, 100, 2)]
+        );
+    }
+
+    #[test]
+    fn test_sample_that_is_an_ancestor() {
+        // Node 0 is a sample and the parent of sample 1.
+        let nodes = vec![Node::new(1), Node::new(0)];
+        let edges = vec![Edge::new(0, 10, 0, 1)];
+        let simplified = simplify(&[0, 1], &nodes, &edges, 10).unwrap();
+        assert_eq!(simplified.nodes.len(), 2);
+        assert_eq!(simplified.edges, vec![Edge::new(0, 10, 0, 1)]);
+    }
+
+    #[test]
+    fn test_edges_are_squashed() {
+        let nodes = vec![Node::new(1), Node::new(0), Node::new(0)];
+        let edges = vec![
+            Edge::new(0, 5, 0, 1),
+            Edge::new(5, 10, 0, 1),
+            Edge::new(0, 10, 0, 2),
+        ];
+        let simplified = simplify(&[1, 2], &nodes, &edges, 10).unwrap();
+        assert_eq!(
+            simplified.edges,
+            vec![Edge::new(0, 10, 2, 0), Edge::new(0, 10, 2, 1)]
+        );
+    }
+
+    #[test]
+    fn test_bad_node_order() {
+        let nodes = vec![Node::new(0), Node::new(1)];
+        let edges = vec![Edge::new(0, 10, 1, 0)];
+        assert!(simplify(&[0], &nodes, &edges, 10).is_err());
+    }
+}
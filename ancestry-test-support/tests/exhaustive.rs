@@ -0,0 +1,76 @@
+use ancestry_inline_simplification::Population;
+use ancestry_test_support::*;
+use neutral_evolution::{replay, ScriptedBirth};
+use tskit_evolution::{EdgeStrategy, EvolvableTableCollection, SimplificationPolicy};
+
+fn check_pedigree(
+    popsize: usize,
+    genome_length: LargeSignedInteger,
+    simplification_interval: LargeSignedInteger,
+    edge_strategy: EdgeStrategy,
+    generations: &[Vec<ScriptedBirth>],
+) {
+    let mut pop = Population::new(popsize as i32, genome_length).unwrap();
+    let mut evolveable_tables = EvolvableTableCollection::new(
+        genome_length,
+        popsize as i32,
+        SimplificationPolicy::Interval(simplification_interval),
+        edge_strategy,
+    )
+    .unwrap();
+    replay(generations, &mut pop).unwrap_or_else(|e| panic!("{:?}: {}", generations, e));
+    replay(generations, &mut evolveable_tables).unwrap();
+    let pop_tables = tskit::TableCollection::try_from(pop).unwrap();
+    let tables = tskit::TableCollection::from(evolveable_tables);
+    if let Err(e) = tskit_evolution::compare_genealogies_by_sample_order(&pop_tables, &tables) {
+        panic!(
+            "popsize = {}, genome_length = {}, simplification_interval = {}, {:?}: {}\n{:?}",
+            popsize, genome_length, simplification_interval, edge_strategy, e, generations
+        );
+    }
+}
+
+fn check_all_pedigrees(popsize: usize, genome_length: LargeSignedInteger, nsteps: usize) {
+    for generations in ExhaustivePedigrees::new(popsize, genome_length, nsteps).unwrap() {
+        for simplification_interval in [1, nsteps as LargeSignedInteger] {
+            for edge_strategy in [EdgeStrategy::IncrementalSort, EdgeStrategy::Buffer] {
+                check_pedigree(
+                    popsize,
+                    genome_length,
+                    simplification_interval,
+                    edge_strategy,
+                    &generations,
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn test_all_pedigrees_popsize_2() {
+    for nsteps in 1..4 {
+        check_all_pedigrees(2, 1, nsteps);
+        check_all_pedigrees(2, 2, nsteps);
+    }
+    for nsteps in 1..3 {
+        check_all_pedigrees(2, 3, nsteps);
+    }
+}
+
+#[test]
+fn test_all_pedigrees_popsize_3() {
+    for nsteps in 1..3 {
+        check_all_pedigrees(3, 1, nsteps);
+    }
+    check_all_pedigrees(3, 2, 1);
+}
+
+// Slow: run with --ignored
+#[test]
+#[ignore]
+fn test_all_pedigrees_large() {
+    check_all_pedigrees(2, 2, 4);
+    check_all_pedigrees(2, 4, 2);
+    check_all_pedigrees(3, 1, 3);
+    check_all_pedigrees(3, 2, 2);
+}
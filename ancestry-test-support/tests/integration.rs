@@ -0,0 +1,187 @@
+use ancestry_inline_simplification::Population;
+use ancestry_test_support::*;
+use neutral_evolution::{evolve, Parameters};
+use tskit::prelude::*;
+use tskit_evolution::{EdgeStrategy, EvolvableTableCollection, SimplificationPolicy};
+
+type CanonicalEdge = (
+    LargeSignedInteger,
+    LargeSignedInteger,
+    LargeSignedInteger,
+    LargeSignedInteger,
+);
+
+// Node IDs differ between implementations, so we compare
+// sorted node times and edges keyed by (left, right, parent time, child time).
+// Times are shifted so that the youngest node has time zero.
+#[derive(Debug, PartialEq)]
+struct CanonicalTables {
+    node_times: Vec<LargeSignedInteger>,
+    edges: Vec<CanonicalEdge>,
+}
+
+impl CanonicalTables {
+    fn new(
+        node_times: Vec<LargeSignedInteger>,
+        edges: impl Iterator<Item = (LargeSignedInteger, LargeSignedInteger, usize, usize)>,
+    ) -> Self {
+        let min_time = node_times.iter().copied().min().unwrap_or(0);
+        let mut edges = edges
+            .map(|(left, right, parent, child)| {
+                (
+                    left,
+                    right,
+                    node_times[parent] - min_time,
+                    node_times[child] - min_time,
+                )
+            })
+            .collect::<Vec<_>>();
+        edges.sort_unstable();
+        let mut node_times = node_times
+            .into_iter()
+            .map(|t| t - min_time)
+            .collect::<Vec<_>>();
+        node_times.sort_unstable();
+        Self { node_times, edges }
+    }
+
+    fn from_reference(simplified: &SimplifiedTables) -> Self {
+        Self::new(
+            simplified.nodes.iter().map(|n| n.time).collect(),
+            simplified
+                .edges
+                .iter()
+                .map(|e| (e.left, e.right, e.parent, e.child)),
+        )
+    }
+
+    fn from_tables(tables: &tskit::TableCollection) -> Self {
+        Self::new(
+            tables
+                .nodes_iter()
+                .map(|n| f64::from(n.time) as LargeSignedInteger)
+                .collect(),
+            tables.edges_iter().map(|e| {
+                (
+                    f64::from(e.left) as LargeSignedInteger,
+                    f64::from(e.right) as LargeSignedInteger,
+                    usize::from(e.parent),
+                    usize::from(e.child),
+                )
+            }),
+        )
+    }
+}
+
+// Reorder nodes from oldest to youngest, as required by the reference implementation,
+// and simplify with respect to the sample nodes.
+fn simplify_tables_with_reference(tables: &tskit::TableCollection) -> SimplifiedTables {
+    let nodes = tables
+        .nodes_iter()
+        .map(|n| (f64::from(n.time) as LargeSignedInteger, n.flags.is_sample()))
+        .collect::<Vec<_>>();
+    let mut order = (0..nodes.len()).collect::<Vec<_>>();
+    order.sort_by_key(|&i| std::cmp::Reverse(nodes[i].0));
+    let mut remap = vec![0; nodes.len()];
+    for (new, &old) in order.iter().enumerate() {
+        remap[old] = new;
+    }
+    let reordered = order
+        .iter()
+        .map(|&i| Node::new(nodes[i].0))
+        .collect::<Vec<_>>();
+    let samples = order
+        .iter()
+        .enumerate()
+        .filter(|(_, &old)| nodes[old].1)
+        .map(|(new, _)| new)
+        .collect::<Vec<_>>();
+    let edges = tables
+        .edges_iter()
+        .map(|e| {
+            Edge::new(
+                f64::from(e.left) as LargeSignedInteger,
+                f64::from(e.right) as LargeSignedInteger,
+                remap[usize::from(e.parent)],
+                remap[usize::from(e.child)],
+            )
+        })
+        .collect::<Vec<_>>();
+    let genome_length = f64::from(tables.sequence_length()) as LargeSignedInteger;
+    simplify(&samples, &reordered, &edges, genome_length).unwrap()
+}
+
+#[test]
+fn test_tskit_backend_matches_reference() {
+    for seed in [101, 201, 301, 8512389] {
+        for pdeath in [1.0, 0.5, 0.25] {
+            for simplification_interval in [1, 7] {
+                let mut pedigree = RecordedPedigree::new(10, 100).unwrap();
+                let mut evolveable_tables = EvolvableTableCollection::new(
+                    100,
+                    10,
+                    SimplificationPolicy::Interval(simplification_interval),
+                    EdgeStrategy::IncrementalSort,
+                )
+                .unwrap();
+                let p = Parameters::new(pdeath, 1e-1, 100).unwrap();
+                evolve(seed, p, &mut pedigree).unwrap();
+                evolve(seed, p, &mut evolveable_tables).unwrap();
+                let tables = tskit::TableCollection::from(evolveable_tables);
+                let reference = pedigree.simplified().unwrap();
+                assert_eq!(
+                    CanonicalTables::from_tables(&tables),
+                    CanonicalTables::from_reference(&reference),
+                    "{} {} {}",
+                    seed,
+                    pdeath,
+                    simplification_interval
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn test_inline_backend_matches_reference_nonoverlapping_gens() {
+    for seed in [101, 201, 301, 401, 8512389, 12853581239, 95192] {
+        let mut pedigree = RecordedPedigree::new(10, 100).unwrap();
+        let mut pop = Population::new(10, 100).unwrap();
+        let p = Parameters::new(1.0, 1e-3, 250).unwrap();
+        evolve(seed, p, &mut pedigree).unwrap();
+        evolve(seed, p, &mut pop).unwrap();
+        let pop_tables = tskit::TableCollection::try_from(pop).unwrap();
+        let reference = pedigree.simplified().unwrap();
+        assert_eq!(
+            CanonicalTables::from_tables(&pop_tables),
+            CanonicalTables::from_reference(&reference),
+            "{}",
+            seed
+        );
+    }
+}
+
+// The inline backend is not fully simplified for overlapping
+// generations, so we simplify its output first.
+#[test]
+fn test_inline_backend_matches_reference_overlapping_gens() {
+    for seed in [101, 201, 301, 401, 8512389, 12853581239, 95192] {
+        for pdeath in [0.25, 0.5, 0.75, 0.9] {
+            let mut pedigree = RecordedPedigree::new(10, 100).unwrap();
+            let mut pop = Population::new(10, 100).unwrap();
+            let p = Parameters::new(pdeath, 1e-1, 250).unwrap();
+            evolve(seed, p, &mut pedigree).unwrap();
+            evolve(seed, p, &mut pop).unwrap();
+            let pop_tables = tskit::TableCollection::try_from(pop).unwrap();
+            let resimplified = simplify_tables_with_reference(&pop_tables);
+            let reference = pedigree.simplified().unwrap();
+            assert_eq!(
+                CanonicalTables::from_reference(&resimplified),
+                CanonicalTables::from_reference(&reference),
+                "{} {}",
+                seed,
+                pdeath
+            );
+        }
+    }
+}
@@ -1,11 +1,15 @@
 use ancestry_common::{LargeSignedInteger, SignedInteger};
 use ancestry_inline_simplification::Population;
 use clap::Parser;
-use neutral_evolution::{evolve, Parameters};
+use neutral_evolution::{evolve, EvolveAncestry, Parameters};
 use tskit::TableAccess;
-use tskit_evolution::EvolvableTableCollection;
+use tskit_evolution::{EvolvableTableCollection, SimplificationPolicy};
 
-#[derive(clap::Parser, Copy, Clone)]
+mod measure;
+mod phases;
+mod record;
+
+#[derive(clap::Parser, Clone)]
 struct Args {
     #[clap(subcommand)]
     simulator: Simulator,
@@ -26,59 +30,691 @@ struct Args {
     death_probability: f64,
     #[clap(long, short = 'S', default_value_t = 101)]
     seed: u64,
+    #[clap(
+        long,
+        help = "Write the tables to this .trees file after simulating, with the alive nodes as samples"
+    )]
+    output: Option<std::path::PathBuf>,
+    #[clap(
+        long,
+        help = "Write genotypes of the alive nodes to this VCF file after simulating"
+    )]
+    vcf: Option<std::path::PathBuf>,
+    #[clap(
+        long,
+        help = "Mutation rate per unit genome length per step, for --vcf (must be > 0)",
+        default_value_t = 0.0
+    )]
+    mutation_rate: f64,
+    #[clap(
+        long,
+        value_enum,
+        help = "Print a record of each run (parameters, time, memory and table sizes) in this format"
+    )]
+    format: Option<record::Format>,
+    #[clap(
+        long,
+        help = "Print the time spent in each phase of the simulation to stderr (needs --features timing)"
+    )]
+    phase_times: bool,
 }
 
-#[derive(clap::Subcommand, Clone, Copy)]
+#[derive(clap::Subcommand, Clone)]
 enum Simulator {
     Tskit(Tskit),
-    Dynamic,
+    Dynamic(Dynamic),
+    /// Run both simulators and check that their genealogies are
+    /// equivalent, exiting with an error if not. Cannot be used with
+    /// --output or --vcf.
+    /// Both run in this process, so unless the peak RSS can be reset
+    /// (peak_rss_reset), that of tskit includes the dynamic run.
+    Compare(Tskit),
+    /// Run every combination of the given values, each in a new process,
+    /// printing one record per run (as CSV unless --format is given).
+    /// Options without a list of values keep their single value.
+    Sweep(Sweep),
 }
 
 #[derive(clap::Parser, Clone, Copy)]
+#[clap(group(
+    clap::ArgGroup::new("simplification_policy")
+        .required(true)
+        .args(&["simplification-interval", "edge-rows", "memory-budget", "adaptive"])
+))]
 struct Tskit {
     #[clap(
         long,
         short,
         help = "Number of death/birth steps between table simplifications"
     )]
-    simplification_interval: LargeSignedInteger,
+    simplification_interval: Option<LargeSignedInteger>,
+    #[clap(
+        long,
+        help = "Simplify once the edge table has at least this many rows"
+    )]
+    edge_rows: Option<u64>,
+    #[clap(
+        long,
+        help = "Simplify once the tables use at least this many bytes (estimated)"
+    )]
+    memory_budget: Option<usize>,
+    #[clap(
+        long,
+        value_name = "INITIAL_INTERVAL",
+        help = "Tune the simplification interval as the simulation runs, up to --nsteps"
+    )]
+    adaptive: Option<LargeSignedInteger>,
+    #[clap(
+        long,
+        value_enum,
+        help = "How edges are made ready for simplification",
+        default_value_t = EdgeStrategy::IncrementalSort
+    )]
+    edge_strategy: EdgeStrategy,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, PartialEq, Eq)]
+enum EdgeStrategy {
+    IncrementalSort,
+    FullSort,
+    Buffer,
+}
+
+impl From<EdgeStrategy> for tskit_evolution::EdgeStrategy {
+    fn from(value: EdgeStrategy) -> Self {
+        match value {
+            EdgeStrategy::IncrementalSort => Self::IncrementalSort,
+            EdgeStrategy::FullSort => Self::FullSort,
+            EdgeStrategy::Buffer => Self::Buffer,
+        }
+    }
+}
+
+impl Tskit {
+    fn simplification_policy(&self, nsteps: LargeSignedInteger) -> SimplificationPolicy {
+        if let Some(n) = self.simplification_interval {
+            SimplificationPolicy::Interval(n)
+        } else if let Some(n) = self.edge_rows {
+            SimplificationPolicy::EdgeRows(n)
+        } else if let Some(bytes) = self.memory_budget {
+            SimplificationPolicy::MemoryBudget(bytes)
+        } else if let Some(initial_interval) = self.adaptive {
+            SimplificationPolicy::Adaptive {
+                initial_interval,
+                max_interval: nsteps,
+            }
+        } else {
+            unreachable!("clap requires one simplification policy")
+        }
+    }
+
+    // The policy as it was given on the command line.
+    fn simplification_policy_label(&self) -> String {
+        if let Some(n) = self.simplification_interval {
+            format!("simplification-interval={}", n)
+        } else if let Some(n) = self.edge_rows {
+            format!("edge-rows={}", n)
+        } else if let Some(bytes) = self.memory_budget {
+            format!("memory-budget={}", bytes)
+        } else if let Some(initial_interval) = self.adaptive {
+            format!("adaptive={}", initial_interval)
+        } else {
+            unreachable!("clap requires one simplification policy")
+        }
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, PartialEq, Eq)]
+enum SweepSimulator {
+    Dynamic,
+    Tskit,
+}
+
+#[derive(clap::Parser, Clone)]
+struct Sweep {
+    #[clap(
+        long,
+        value_enum,
+        value_delimiter = ',',
+        default_values = &["dynamic", "tskit"],
+        help = "Simulators to run"
+    )]
+    simulators: Vec<SweepSimulator>,
+    #[clap(long, value_delimiter = ',', help = "Values of --popsize")]
+    popsizes: Vec<SignedInteger>,
+    #[clap(long, value_delimiter = ',', help = "Values of --rho")]
+    rhos: Vec<f64>,
+    #[clap(long, value_delimiter = ',', help = "Values of --sequence-length")]
+    sequence_lengths: Vec<LargeSignedInteger>,
+    #[clap(long, value_delimiter = ',', help = "Values of --death-probability")]
+    death_probabilities: Vec<f64>,
+    #[clap(
+        long,
+        value_delimiter = ',',
+        help = "Values of tskit --simplification-interval (required for tskit)"
+    )]
+    simplification_intervals: Vec<LargeSignedInteger>,
+    #[clap(long, value_delimiter = ',', help = "Values of --seed")]
+    seeds: Vec<u64>,
+    #[clap(
+        long,
+        value_enum,
+        help = "tskit --edge-strategy [default: incremental-sort]"
+    )]
+    edge_strategy: Option<EdgeStrategy>,
+}
+
+// The name of a value on the command line.
+fn value_name<T: clap::ValueEnum>(value: &T) -> &'static str {
+    value.to_possible_value().unwrap().get_name()
+}
+
+#[derive(clap::Parser, Clone, Copy)]
+struct Dynamic {
+    #[clap(
+        long,
+        help = "Print graph statistics after each step (adds output to the timings)"
+    )]
+    graph_stats: bool,
+}
+
+// Reports the graph after each simplification.
+struct GraphStatsReporter<'a> {
+    population: &'a mut Population,
+}
+
+impl<'a> EvolveAncestry for GraphStatsReporter<'a> {
+    fn genome_length(&self) -> LargeSignedInteger {
+        self.population.genome_length()
+    }
+
+    fn setup(&mut self, final_time: LargeSignedInteger) {
+        println!("step\treachable_nodes\tancestry_segments\tchild_segments\troots");
+        self.population.setup(final_time)
+    }
+
+    fn generate_deaths(&mut self, death: &mut neutral_evolution::Death) -> usize {
+        self.population.generate_deaths(death)
+    }
+
+    fn current_population_size(&self) -> usize {
+        self.population.current_population_size()
+    }
+
+    fn record_birth(
+        &mut self,
+        birth_time: LargeSignedInteger,
+        final_timepoint: LargeSignedInteger,
+        breakpoints: &[neutral_evolution::TransmittedSegment],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.population
+            .record_birth(birth_time, final_timepoint, breakpoints)
+    }
+
+    fn record_mutations(
+        &mut self,
+        birth_time: LargeSignedInteger,
+        final_timepoint: LargeSignedInteger,
+        mutations: &[neutral_evolution::Mutation],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.population
+            .record_mutations(birth_time, final_timepoint, mutations)
+    }
+
+    fn record_provenance(
+        &mut self,
+        provenance: &neutral_evolution::Provenance,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.population.record_provenance(provenance)
+    }
+
+    fn simplify(
+        &mut self,
+        current_time_point: LargeSignedInteger,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.population.simplify(current_time_point)?;
+        let stats = self.population.graph_stats();
+        println!(
+            "{}\t{}\t{}\t{}\t{}",
+            current_time_point,
+            stats.num_reachable_nodes,
+            stats.num_ancestry_segments,
+            stats.num_child_segments,
+            stats.num_roots
+        );
+        Ok(())
+    }
+
+    fn finish(
+        &mut self,
+        current_time_point: LargeSignedInteger,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.population.finish(current_time_point)
+    }
 }
 
 fn evolve_wrapper<T: neutral_evolution::EvolveAncestry>(
+    simulator: &str,
     parameters: Parameters,
-    args: Args,
+    args: &Args,
     population: &mut T,
 ) {
     evolve(args.seed, parameters, population).unwrap();
+    let phases = ancestry_common::timing::take().unwrap();
+    if args.phase_times {
+        phases::print(simulator, &phases);
+    }
+}
+
+// Sorting only the new edges is not always enough,
+// so say how often everything was sorted instead.
+fn report_full_sort_fallbacks(tskit: &Tskit, population: &EvolvableTableCollection) {
+    if tskit.edge_strategy == EdgeStrategy::IncrementalSort {
+        eprintln!(
+            "full sort fallbacks: {}",
+            population.num_full_sort_fallbacks()
+        );
+    }
+}
+
+fn make_record(
+    simulator: &str,
+    args: &Args,
+    tskit: Option<&Tskit>,
+    measurement: measure::Measurement,
+    tables: &tskit::TableCollection,
+) -> record::Record {
+    record::Record {
+        simulator: simulator.to_string(),
+        popsize: args.popsize,
+        rho: args.rho,
+        sequence_length: args.sequence_length,
+        nsteps: args.nsteps,
+        death_probability: args.death_probability,
+        seed: args.seed,
+        simplification_policy: tskit.map(|t| t.simplification_policy_label()),
+        edge_strategy: tskit.map(|t| value_name(&t.edge_strategy).to_string()),
+        measurement,
+        num_nodes: u64::from(tables.nodes().num_rows()),
+        num_edges: u64::from(tables.edges().num_rows()),
+    }
+}
+
+// Expands the sweep into the arguments of each run.
+fn sweep_runs(sweep: &Sweep, args: &Args) -> Vec<Vec<String>> {
+    fn values<T: ToString>(values: &[T], default: T) -> Vec<String> {
+        if values.is_empty() {
+            vec![default.to_string()]
+        } else {
+            values.iter().map(|v| v.to_string()).collect()
+        }
+    }
+
+    // The records of each run are parsed, so always ask for JSON.
+    let mut runs = vec![vec![
+        "--format".to_string(),
+        value_name(&record::Format::Json).to_string(),
+        "--nsteps".to_string(),
+        args.nsteps.to_string(),
+    ]];
+    if args.phase_times {
+        runs[0].push("--phase-times".to_string());
+    }
+    for (option, values) in [
+        ("--popsize", values(&sweep.popsizes, args.popsize)),
+        ("--rho", values(&sweep.rhos, args.rho)),
+        (
+            "--sequence-length",
+            values(&sweep.sequence_lengths, args.sequence_length),
+        ),
+        (
+            "--death-probability",
+            values(&sweep.death_probabilities, args.death_probability),
+        ),
+        ("--seed", values(&sweep.seeds, args.seed)),
+    ] {
+        runs = runs
+            .iter()
+            .flat_map(|run| {
+                values.iter().map(move |value| {
+                    let mut run = run.clone();
+                    run.extend([option.to_string(), value.clone()]);
+                    run
+                })
+            })
+            .collect();
+    }
+
+    let mut simulators = vec![];
+    for simulator in sweep.simulators.iter() {
+        match simulator {
+            SweepSimulator::Dynamic => simulators.push(vec!["dynamic".to_string()]),
+            SweepSimulator::Tskit => {
+                for interval in sweep.simplification_intervals.iter() {
+                    simulators.push(vec![
+                        "tskit".to_string(),
+                        "--simplification-interval".to_string(),
+                        interval.to_string(),
+                        "--edge-strategy".to_string(),
+                        value_name(&sweep.edge_strategy.unwrap_or(EdgeStrategy::IncrementalSort))
+                            .to_string(),
+                    ]);
+                }
+            }
+        }
+    }
+    runs.iter()
+        .flat_map(|run| {
+            simulators.iter().map(move |simulator| {
+                let mut run = run.clone();
+                run.extend(simulator.iter().cloned());
+                run
+            })
+        })
+        .collect()
+}
+
+fn run_sweep(sweep: &Sweep, args: &Args) {
+    use clap::CommandFactory;
+    if sweep.simulators.contains(&SweepSimulator::Tskit) {
+        if sweep.simplification_intervals.is_empty() {
+            Args::command()
+                .error(
+                    clap::ErrorKind::MissingRequiredArgument,
+                    "sweeping the tskit simulator requires --simplification-intervals",
+                )
+                .exit();
+        }
+    } else if !sweep.simplification_intervals.is_empty() || sweep.edge_strategy.is_some() {
+        Args::command()
+            .error(
+                clap::ErrorKind::ArgumentConflict,
+                "--simplification-intervals and --edge-strategy only apply to the tskit simulator",
+            )
+            .exit();
+    }
+    let exe = std::env::current_exe().unwrap();
+    let mut records = vec![];
+    for run in sweep_runs(sweep, args).iter() {
+        let output = std::process::Command::new(&exe)
+            .args(run)
+            .stderr(std::process::Stdio::inherit())
+            .output()
+            .unwrap();
+        if !output.status.success() {
+            eprintln!("run failed: {}", run.join(" "));
+            std::process::exit(output.status.code().unwrap_or(1));
+        }
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        for line in stdout.lines() {
+            match record::Record::from_json(line) {
+                Ok(record) => records.push(record),
+                Err(e) => {
+                    eprintln!("run printed {}: {}", e, run.join(" "));
+                    std::process::exit(1);
+                }
+            }
+        }
+    }
+    record::print(&records, args.format.unwrap_or(record::Format::Csv));
+}
+
+fn write_tables(mut tables: tskit::TableCollection, args: &Args) {
+    if let Some(path) = &args.output {
+        tables.build_index().unwrap();
+        tables
+            .dump(path.to_str().unwrap(), tskit::TableOutputOptions::default())
+            .unwrap();
+    }
+    write_vcf(tables, args);
+}
+
+// Mutations are placed after the simulation so that
+// they do not affect the timings (or the --output).
+fn write_vcf(mut tables: tskit::TableCollection, args: &Args) {
+    if let Some(path) = &args.vcf {
+        tskit_evolution::place_mutations(&mut tables, args.mutation_rate, None, args.seed).unwrap();
+        let mut writer = std::io::BufWriter::new(std::fs::File::create(path).unwrap());
+        // The simulation is haploid, so each alive node is an individual.
+        tskit_evolution::write_vcf(&tables, 1, &mut writer).unwrap();
+    }
 }
 
 fn main() {
     let args = Args::parse();
 
+    if args.phase_times && !ancestry_common::timing::ENABLED {
+        use clap::CommandFactory;
+        Args::command()
+            .error(
+                clap::ErrorKind::ArgumentConflict,
+                "--phase-times requires building the benchmark with --features timing",
+            )
+            .exit();
+    }
+
+    if args.vcf.is_some() && args.mutation_rate <= 0.0 {
+        use clap::CommandFactory;
+        Args::command()
+            .error(
+                clap::ErrorKind::InvalidValue,
+                "--vcf requires a --mutation-rate > 0, or the VCF would have no records",
+            )
+            .exit();
+    }
+
+    if matches!(args.simulator, Simulator::Compare(_))
+        && (args.output.is_some() || args.vcf.is_some())
+    {
+        use clap::CommandFactory;
+        Args::command()
+            .error(
+                clap::ErrorKind::ArgumentConflict,
+                "--output and --vcf cannot be used with compare, which runs both simulators",
+            )
+            .exit();
+    }
+
     let c = args.rho / 4.0 / (args.popsize as f64);
 
     let parameters = Parameters::new(args.death_probability, c, args.nsteps).unwrap();
 
-    match args.simulator {
+    match &args.simulator {
         Simulator::Tskit(t) => {
-            let mut population = EvolvableTableCollection::new(
-                args.sequence_length,
-                args.popsize,
-                t.simplification_interval,
-            )
-            .unwrap();
-            evolve_wrapper(parameters, args, &mut population);
+            let (population, measurement) = measure::measure(|| {
+                let mut population = EvolvableTableCollection::new(
+                    args.sequence_length,
+                    args.popsize,
+                    t.simplification_policy(args.nsteps),
+                    t.edge_strategy.into(),
+                )
+                .unwrap();
+                evolve_wrapper("tskit", parameters, &args, &mut population);
+                population
+            });
+            report_full_sort_fallbacks(t, &population);
             let tables = tskit::TableCollection::from(population);
-            println!(
-                "nodes: {}, edges: {}",
-                tables.nodes().num_rows(),
-                tables.edges().num_rows()
-            );
+            if let Some(format) = args.format {
+                record::print(
+                    &[make_record("tskit", &args, Some(t), measurement, &tables)],
+                    format,
+                );
+            } else {
+                println!(
+                    "nodes: {}, edges: {}",
+                    tables.nodes().num_rows(),
+                    tables.edges().num_rows()
+                );
+            }
+            write_tables(tables, &args);
+        }
+        Simulator::Compare(t) => {
+            let (dynamic, dynamic_measurement) = measure::measure(|| {
+                let mut population = Population::new(args.popsize, args.sequence_length).unwrap();
+                evolve_wrapper("dynamic", parameters, &args, &mut population);
+                population
+            });
+            let dynamic = tskit::TableCollection::try_from(dynamic).unwrap();
+            let (tskit, tskit_measurement) = measure::measure(|| {
+                let mut population = EvolvableTableCollection::new(
+                    args.sequence_length,
+                    args.popsize,
+                    t.simplification_policy(args.nsteps),
+                    t.edge_strategy.into(),
+                )
+                .unwrap();
+                evolve_wrapper("tskit", parameters, &args, &mut population);
+                population
+            });
+            report_full_sort_fallbacks(t, &tskit);
+            if !tskit_measurement.peak_rss_reset {
+                eprintln!(
+                    "could not reset the peak RSS, so that of tskit includes the dynamic run"
+                );
+            }
+            let tskit = tskit::TableCollection::from(tskit);
+
+            if let Some(format) = args.format {
+                record::print(
+                    &[
+                        make_record("dynamic", &args, None, dynamic_measurement, &dynamic),
+                        make_record("tskit", &args, Some(t), tskit_measurement, &tskit),
+                    ],
+                    format,
+                );
+            } else {
+                println!("simulator\tseconds\tpeak_rss_kb\tpeak_rss_reset");
+                for (name, m) in [
+                    ("dynamic", &dynamic_measurement),
+                    ("tskit", &tskit_measurement),
+                ] {
+                    let peak_rss = m.peak_rss.map_or("NA".to_string(), |kb| kb.to_string());
+                    println!(
+                        "{}\t{:.3}\t{}\t{}",
+                        name, m.seconds, peak_rss, m.peak_rss_reset
+                    );
+                }
+            }
+            if let Err(e) = tskit_evolution::compare_genealogies_by_sample_order(&dynamic, &tskit) {
+                eprintln!("genealogies differ: {}", e);
+                std::process::exit(1);
+            }
+            println!("genealogies are equivalent");
         }
-        Simulator::Dynamic => {
-            let mut population = Population::new(args.popsize, args.sequence_length).unwrap();
-            evolve_wrapper(parameters, args, &mut population);
-            println!("num still reachable = {}", population.num_still_reachable());
+        Simulator::Dynamic(d) => {
+            let (population, measurement) = measure::measure(|| {
+                let mut population = Population::new(args.popsize, args.sequence_length).unwrap();
+                if d.graph_stats {
+                    let mut reporter = GraphStatsReporter {
+                        population: &mut population,
+                    };
+                    evolve_wrapper("dynamic", parameters, &args, &mut reporter);
+                } else {
+                    evolve_wrapper("dynamic", parameters, &args, &mut population);
+                }
+                population
+            });
+            if let Some(format) = args.format {
+                let tables = tskit::TableCollection::try_from(population).unwrap();
+                record::print(
+                    &[make_record("dynamic", &args, None, measurement, &tables)],
+                    format,
+                );
+                write_tables(tables, &args);
+            } else {
+                println!("num still reachable = {}", population.num_still_reachable());
+                if args.output.is_some() || args.vcf.is_some() {
+                    write_tables(tskit::TableCollection::try_from(population).unwrap(), &args);
+                }
+            }
         }
+        Simulator::Sweep(sweep) => run_sweep(sweep, &args),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn runs(command_line: &[&str]) -> Vec<String> {
+        let args = Args::try_parse_from(command_line).unwrap();
+        match &args.simulator {
+            Simulator::Sweep(sweep) => sweep_runs(sweep, &args)
+                .iter()
+                .map(|run| run.join(" "))
+                .collect(),
+            _ => panic!("not a sweep"),
+        }
+    }
+
+    #[test]
+    fn test_sweep_runs() {
+        let runs = runs(&[
+            "benchmark",
+            "-N",
+            "10",
+            "-r",
+            "5",
+            "-n",
+            "20",
+            "sweep",
+            "--popsizes",
+            "10,20",
+            "--seeds",
+            "1,2",
+            "--simplification-intervals",
+            "1,10",
+            "--edge-strategy",
+            "buffer",
+        ]);
+        // 2 popsizes x 2 seeds x (dynamic + 2 tskit intervals)
+        assert_eq!(runs.len(), 12);
+        assert_eq!(
+            runs[0],
+            "--format json --nsteps 20 --popsize 10 --rho 5 --sequence-length 10000 \
+             --death-probability 1 --seed 1 dynamic"
+        );
+        assert_eq!(
+            runs[2],
+            "--format json --nsteps 20 --popsize 10 --rho 5 --sequence-length 10000 \
+             --death-probability 1 --seed 1 tskit --simplification-interval 10 \
+             --edge-strategy buffer"
+        );
+        assert_eq!(
+            runs[11],
+            "--format json --nsteps 20 --popsize 20 --rho 5 --sequence-length 10000 \
+             --death-probability 1 --seed 2 tskit --simplification-interval 10 \
+             --edge-strategy buffer"
+        );
+        let mut unique = runs.clone();
+        unique.sort();
+        unique.dedup();
+        assert_eq!(unique.len(), runs.len());
+    }
+
+    #[test]
+    fn test_sweep_runs_defaults() {
+        let runs = runs(&[
+            "benchmark",
+            "-N",
+            "10",
+            "-r",
+            "5",
+            "-n",
+            "20",
+            "sweep",
+            "--simulators",
+            "tskit",
+            "--simplification-intervals",
+            "3",
+        ]);
+        assert_eq!(
+            runs,
+            vec![
+                "--format json --nsteps 20 --popsize 10 --rho 5 --sequence-length 10000 \
+                 --death-probability 1 --seed 101 tskit --simplification-interval 3 \
+                 --edge-strategy incremental-sort"
+            ]
+        );
     }
 }
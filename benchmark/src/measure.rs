@@ -0,0 +1,42 @@
+// Wall time and peak memory, measured by the process itself.
+// Peak memory is only available on Linux.
+
+pub struct Measurement {
+    pub seconds: f64,
+    // Peak resident set size, in kB.
+    pub peak_rss: Option<u64>,
+    // Whether the peak RSS was reset before measuring. If not,
+    // it is the peak since the process started, which includes
+    // anything measured before in the same process.
+    pub peak_rss_reset: bool,
+}
+
+// Runs `f` and measures it. The peak RSS is reset first (if the
+// kernel allows it), so that it covers only `f` and what is already
+// resident; otherwise it is the peak since the process started.
+pub fn measure<T>(f: impl FnOnce() -> T) -> (T, Measurement) {
+    let peak_rss_reset = std::fs::write("/proc/self/clear_refs", "5").is_ok();
+    let start = std::time::Instant::now();
+    let rv = f();
+    let seconds = start.elapsed().as_secs_f64();
+    (
+        rv,
+        Measurement {
+            seconds,
+            peak_rss: peak_rss(),
+            peak_rss_reset,
+        },
+    )
+}
+
+fn peak_rss() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmHWM:"))?
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .ok()
+}
@@ -0,0 +1,30 @@
+// The per-phase breakdown for --phase-times.
+
+use ancestry_common::timing::PhaseTime;
+
+// Prints the phases as an indented tree, with the share of
+// the total time spent in the outermost phases.
+pub fn print(simulator: &str, phases: &[PhaseTime]) {
+    let total: f64 = phases
+        .iter()
+        .filter(|p| p.path.len() == 1)
+        .map(|p| p.seconds)
+        .sum();
+    eprintln!("{} phase\tcalls\tseconds\tpercent", simulator);
+    for p in phases {
+        let indent = "  ".repeat(p.path.len() - 1);
+        let percent = if total > 0.0 {
+            100.0 * p.seconds / total
+        } else {
+            0.0
+        };
+        eprintln!(
+            "{}{}\t{}\t{:.3}\t{:.1}",
+            indent,
+            p.path.last().unwrap(),
+            p.calls,
+            p.seconds,
+            percent
+        );
+    }
+}
@@ -0,0 +1,332 @@
+// Machine-readable results of one run, for --format.
+
+#[derive(clap::ValueEnum, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// One JSON object per line
+    Json,
+    /// A header line, then one line per record
+    Csv,
+}
+
+pub struct Record {
+    pub simulator: String,
+    pub popsize: ancestry_common::SignedInteger,
+    pub rho: f64,
+    pub sequence_length: ancestry_common::LargeSignedInteger,
+    pub nsteps: ancestry_common::LargeSignedInteger,
+    pub death_probability: f64,
+    pub seed: u64,
+    // Only for the tskit simulator.
+    pub simplification_policy: Option<String>,
+    pub edge_strategy: Option<String>,
+    pub measurement: crate::measure::Measurement,
+    pub num_nodes: u64,
+    pub num_edges: u64,
+}
+
+const CSV_HEADER: &str = "simulator,popsize,rho,sequence_length,nsteps,death_probability,seed,\
+simplification_policy,edge_strategy,seconds,peak_rss_kb,peak_rss_reset,num_nodes,num_edges";
+
+pub fn print(records: &[Record], format: Format) {
+    write(records, format, &mut std::io::stdout().lock()).unwrap();
+}
+
+fn write<W: std::io::Write>(
+    records: &[Record],
+    format: Format,
+    out: &mut W,
+) -> std::io::Result<()> {
+    match format {
+        Format::Json => {
+            for r in records {
+                writeln!(out, "{}", r.to_json())?;
+            }
+        }
+        Format::Csv => {
+            writeln!(out, "{}", CSV_HEADER)?;
+            for r in records {
+                writeln!(out, "{}", r.to_csv())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+// A JSON string literal.
+fn json_string(s: &str) -> String {
+    let mut rv = String::with_capacity(s.len() + 2);
+    rv.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => rv.push_str("\\\""),
+            '\\' => rv.push_str("\\\\"),
+            '\n' => rv.push_str("\\n"),
+            '\r' => rv.push_str("\\r"),
+            '\t' => rv.push_str("\\t"),
+            c if c.is_control() => rv.push_str(&format!("\\u{:04x}", c as u32)),
+            c => rv.push(c),
+        }
+    }
+    rv.push('"');
+    rv
+}
+
+// A CSV field, quoted if it contains a separator, quote or line break.
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+impl Record {
+    fn to_json(&self) -> String {
+        let string = |s: &Option<String>| s.as_deref().map_or("null".to_string(), json_string);
+        format!(
+            concat!(
+                r#"{{"simulator":{},"popsize":{},"rho":{},"sequence_length":{},"nsteps":{},"#,
+                r#""death_probability":{},"seed":{},"simplification_policy":{},"edge_strategy":{},"#,
+                r#""seconds":{},"peak_rss_kb":{},"peak_rss_reset":{},"num_nodes":{},"num_edges":{}}}"#
+            ),
+            json_string(&self.simulator),
+            self.popsize,
+            self.rho,
+            self.sequence_length,
+            self.nsteps,
+            self.death_probability,
+            self.seed,
+            string(&self.simplification_policy),
+            string(&self.edge_strategy),
+            self.measurement.seconds,
+            self.measurement
+                .peak_rss
+                .map_or("null".to_string(), |kb| kb.to_string()),
+            self.measurement.peak_rss_reset,
+            self.num_nodes,
+            self.num_edges
+        )
+    }
+
+    fn to_csv(&self) -> String {
+        let string = |s: &Option<String>| s.as_deref().map_or(String::new(), csv_field);
+        format!(
+            "{},{},{},{},{},{},{},{},{},{},{},{},{},{}",
+            csv_field(&self.simulator),
+            self.popsize,
+            self.rho,
+            self.sequence_length,
+            self.nsteps,
+            self.death_probability,
+            self.seed,
+            string(&self.simplification_policy),
+            string(&self.edge_strategy),
+            self.measurement.seconds,
+            self.measurement
+                .peak_rss
+                .map_or(String::new(), |kb| kb.to_string()),
+            self.measurement.peak_rss_reset,
+            self.num_nodes,
+            self.num_edges
+        )
+    }
+
+    // Parses a line printed by to_json.
+    pub fn from_json(line: &str) -> Result<Self, String> {
+        let fields = json_fields(line).ok_or_else(|| format!("not a record: {}", line))?;
+        Ok(Self {
+            simulator: required(&fields, "simulator")?,
+            popsize: required(&fields, "popsize")?,
+            rho: required(&fields, "rho")?,
+            sequence_length: required(&fields, "sequence_length")?,
+            nsteps: required(&fields, "nsteps")?,
+            death_probability: required(&fields, "death_probability")?,
+            seed: required(&fields, "seed")?,
+            simplification_policy: optional(&fields, "simplification_policy")?,
+            edge_strategy: optional(&fields, "edge_strategy")?,
+            measurement: crate::measure::Measurement {
+                seconds: required(&fields, "seconds")?,
+                peak_rss: optional(&fields, "peak_rss_kb")?,
+                peak_rss_reset: required(&fields, "peak_rss_reset")?,
+            },
+            num_nodes: required(&fields, "num_nodes")?,
+            num_edges: required(&fields, "num_edges")?,
+        })
+    }
+}
+
+type Fields = std::collections::HashMap<String, Option<String>>;
+
+fn optional<T: std::str::FromStr>(fields: &Fields, key: &str) -> Result<Option<T>, String> {
+    match fields.get(key) {
+        None => Err(format!("no {} in record", key)),
+        Some(None) => Ok(None),
+        Some(Some(value)) => value
+            .parse()
+            .map(Some)
+            .map_err(|_| format!("invalid {}: {}", key, value)),
+    }
+}
+
+fn required<T: std::str::FromStr>(fields: &Fields, key: &str) -> Result<T, String> {
+    optional(fields, key)?.ok_or_else(|| format!("{} is null", key))
+}
+
+// The fields of a flat JSON object, with null as None.
+// Strings are unescaped; other values are kept as written.
+fn json_fields(line: &str) -> Option<Fields> {
+    let mut chars = line
+        .trim()
+        .strip_prefix('{')?
+        .strip_suffix('}')?
+        .chars()
+        .peekable();
+    let mut fields = std::collections::HashMap::default();
+    let string = |chars: &mut std::iter::Peekable<std::str::Chars>| {
+        let mut s = String::new();
+        loop {
+            match chars.next()? {
+                '"' => return Some(s),
+                '\\' => s.push(match chars.next()? {
+                    'n' => '\n',
+                    'r' => '\r',
+                    't' => '\t',
+                    'b' => '\u{8}',
+                    'f' => '\u{c}',
+                    'u' => {
+                        let mut code = hex4(chars)?;
+                        // A surrogate pair, for characters outside the BMP.
+                        if (0xd800..0xdc00).contains(&code) {
+                            if chars.next()? != '\\' || chars.next()? != 'u' {
+                                return None;
+                            }
+                            let low = hex4(chars)?;
+                            if !(0xdc00..0xe000).contains(&low) {
+                                return None;
+                            }
+                            code = 0x10000 + ((code - 0xd800) << 10) + (low - 0xdc00);
+                        }
+                        char::from_u32(code)?
+                    }
+                    c @ ('"' | '\\' | '/') => c,
+                    _ => return None,
+                }),
+                c => s.push(c),
+            }
+        }
+    };
+    while chars.peek().is_some() {
+        if chars.next()? != '"' {
+            return None;
+        }
+        let key = string(&mut chars)?;
+        if chars.next()? != ':' {
+            return None;
+        }
+        let value = if chars.next_if_eq(&'"').is_some() {
+            Some(string(&mut chars)?)
+        } else {
+            let mut v = String::new();
+            while let Some(c) = chars.next_if(|c| *c != ',') {
+                v.push(c);
+            }
+            (v != "null").then_some(v)
+        };
+        fields.insert(key, value);
+        if chars.next_if_eq(&',').is_none() && chars.peek().is_some() {
+            return None;
+        }
+    }
+    Some(fields)
+}
+
+// The four hex digits of a \u escape.
+fn hex4(chars: &mut std::iter::Peekable<std::str::Chars>) -> Option<u32> {
+    let mut code = 0;
+    for _ in 0..4 {
+        code = code * 16 + chars.next()?.to_digit(16)?;
+    }
+    Some(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(simplification_policy: Option<&str>) -> Record {
+        Record {
+            simulator: "tskit".to_string(),
+            popsize: 100,
+            rho: 1e3,
+            sequence_length: 10000,
+            nsteps: 50,
+            death_probability: 0.5,
+            seed: 101,
+            simplification_policy: simplification_policy.map(|s| s.to_string()),
+            edge_strategy: None,
+            measurement: crate::measure::Measurement {
+                seconds: 1.25,
+                peak_rss: Some(4096),
+                peak_rss_reset: true,
+            },
+            num_nodes: 1234,
+            num_edges: 5678,
+        }
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        for policy in [
+            None,
+            Some("simplification-interval=10"),
+            Some("with \"quotes\", a \\ and\na\ttab \u{1} é 😀"),
+        ] {
+            let r = record(policy);
+            let json = r.to_json();
+            assert!(!json.contains('\n'));
+            let parsed = Record::from_json(&json).unwrap();
+            assert_eq!(parsed.to_json(), json);
+            assert_eq!(parsed.simplification_policy.as_deref(), policy);
+            assert_eq!(parsed.edge_strategy, None);
+            assert_eq!(parsed.measurement.peak_rss, Some(4096));
+        }
+    }
+
+    #[test]
+    fn test_json_escapes() {
+        assert_eq!(json_string("a\"b\\c\nd\u{1}"), r#""a\"b\\c\nd\u0001""#);
+        let fields = json_fields(r#"{"a":"é😀\/","b":null,"c":1.5}"#).unwrap();
+        assert_eq!(fields["a"].as_deref(), Some("é😀/"));
+        assert_eq!(fields["b"], None);
+        assert_eq!(fields["c"].as_deref(), Some("1.5"));
+        assert!(json_fields(r#"{"a":"\x"}"#).is_none());
+        assert!(json_fields(r#"{"a":"\ud83d"}"#).is_none());
+        assert!(Record::from_json(r#"{"simulator":"tskit"}"#).is_err());
+    }
+
+    #[test]
+    fn test_csv() {
+        let records = [record(None), record(Some("a,\"b\""))];
+        let mut out = vec![];
+        write(&records, Format::Csv, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines = out.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], CSV_HEADER);
+        assert_eq!(
+            lines[1],
+            "tskit,100,1000,10000,50,0.5,101,,,1.25,4096,true,1234,5678"
+        );
+        assert_eq!(
+            lines[2],
+            "tskit,100,1000,10000,50,0.5,101,\"a,\"\"b\"\"\",,1.25,4096,true,1234,5678"
+        );
+        assert_eq!(lines[0].split(',').count(), lines[1].split(',').count());
+
+        let mut out = vec![];
+        write(&records, Format::Json, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(out.lines().count(), 2);
+        assert!(out.lines().all(|line| Record::from_json(line).is_ok()));
+    }
+}
@@ -1,10 +1,12 @@
-#!
-
-# NOTE: sudo apt install google-perftools
-
-# WARNING: only works on Ubuntu/Pop_OS! (probably...)
-
-LD_PRELOAD=/usr/lib/x86_64-linux-gnu/libprofiler.so.0 CPUPROFILE=bmark.prof ./target/release/benchmark -N 10000 -r 500 \
--L 10000 -S 101 -n 300 dynamic
-google-pprof -pdf ./target/release/benchmark bmark.prof > bmark.pdf
-
+#!/usr/bin/env bash
+
+# Prints the time spent in each phase of the simulation
+# (deaths, births, sorting, simplification, ...) to stderr.
+cargo build --release -p benchmark --features timing
+./target/release/benchmark -N 10000 -r 500 -L 10000 -S 101 -n 300 --phase-times dynamic
+
+# For a call graph of everything else:
+# sudo apt install google-perftools
+# LD_PRELOAD=/usr/lib/x86_64-linux-gnu/libprofiler.so.0 CPUPROFILE=bmark.prof ./target/release/benchmark -N 10000 -r 500 \
+# -L 10000 -S 101 -n 300 dynamic
+# google-pprof -pdf ./target/release/benchmark bmark.prof > bmark.pdf
@@ -34,6 +34,27 @@ pub trait EvolveAncestry {
         breakpoints: &[TransmittedSegment],
     ) -> Result<(), Box<dyn Error>>;
 
+    /// Record mutations carried by the most recent birth.
+    ///
+    /// Only called when [Parameters] include mutations.
+    /// The default ignores them.
+    fn record_mutations(
+        &mut self,
+        _birth_time: LargeSignedInteger,
+        _final_time: LargeSignedInteger,
+        _mutations: &[Mutation],
+    ) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    /// Record how the simulation is run, before [setup](EvolveAncestry::setup).
+    ///
+    /// Only called by [evolve].
+    /// The default ignores it.
+    fn record_provenance(&mut self, _provenance: &Provenance) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
     fn simplify(&mut self, current_time_point: LargeSignedInteger) -> Result<(), Box<dyn Error>>;
 
     // When simplifying periodically, it is possible to not be simplified when the last time step
@@ -43,27 +64,50 @@ pub trait EvolveAncestry {
 
 type Rng = Rc<RefCell<rand_pcg::Pcg64>>;
 
+enum Fates {
+    Random {
+        rng: Rng,
+        death_probability: f64,
+        uniform: rand::distributions::Uniform<f64>,
+    },
+    // Used by replay: who dies is decided in advance.
+    Scripted(std::vec::IntoIter<bool>),
+}
+
 pub struct Death {
-    rng: Rng,
-    death_probability: f64,
-    uniform: rand::distributions::Uniform<f64>,
+    fates: Fates,
 }
 
 impl Death {
     fn new(death_probability: f64, rng: Rng) -> Self {
         Self {
-            rng,
-            death_probability,
-            uniform: rand::distributions::Uniform::new(0., 1.),
+            fates: Fates::Random {
+                rng,
+                death_probability,
+                uniform: rand::distributions::Uniform::new(0., 1.),
+            },
+        }
+    }
+
+    fn new_scripted(fates: Vec<bool>) -> Self {
+        Self {
+            fates: Fates::Scripted(fates.into_iter()),
         }
     }
 
     pub fn dies(&mut self) -> bool {
-        self.uniform.sample(&mut self.rng.borrow_mut().deref_mut()) <= self.death_probability
+        match &mut self.fates {
+            Fates::Random {
+                rng,
+                death_probability,
+                uniform,
+            } => uniform.sample(&mut rng.borrow_mut().deref_mut()) <= *death_probability,
+            Fates::Scripted(fates) => fates.next().unwrap_or(false),
+        }
     }
 }
 
-#[derive(Eq, PartialEq, Debug)]
+#[derive(Clone, Eq, PartialEq, Debug)]
 pub struct TransmittedSegment {
     pub left: LargeSignedInteger,
     pub right: LargeSignedInteger,
@@ -80,11 +124,49 @@ impl TransmittedSegment {
     }
 }
 
+/// How mutation positions are chosen.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum MutationModel {
+    /// Continuous positions on `[0, genome_length)`,
+    /// so that each mutation is at a new site.
+    InfiniteSites,
+    /// Integer positions on `[0, genome_length)`.
+    /// Sites may mutate more than once.
+    FiniteSites,
+}
+
+/// A new mutation on the genome of a newborn.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Mutation {
+    pub position: f64,
+}
+
+/// A birth in an explicit pedigree.
+///
+/// The child replaces the individual at index `replaces`
+/// and inherits `transmissions` from the alive parents.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct ScriptedBirth {
+    pub replaces: usize,
+    pub transmissions: Vec<TransmittedSegment>,
+}
+
+impl ScriptedBirth {
+    pub fn new(replaces: usize, transmissions: Vec<TransmittedSegment>) -> Self {
+        Self {
+            replaces,
+            transmissions,
+        }
+    }
+}
+
 #[derive(Copy, Clone)]
 pub struct Parameters {
     death_probability: f64,
     mean_num_crossovers: f64,
     nsteps: LargeSignedInteger,
+    mean_num_mutations: f64,
+    mutation_model: MutationModel,
 }
 
 impl Parameters {
@@ -122,8 +204,69 @@ impl Parameters {
             death_probability,
             mean_num_crossovers,
             nsteps,
+            mean_num_mutations: 0.0,
+            mutation_model: MutationModel::InfiniteSites,
         })
     }
+
+    /// Add neutral mutations to each new genome.
+    ///
+    /// The number of mutations per birth is Poisson
+    /// with mean `mean_num_mutations`.
+    pub fn with_mutations(
+        self,
+        mean_num_mutations: f64,
+        mutation_model: MutationModel,
+    ) -> Result<Self, ParameterError> {
+        if !mean_num_mutations.is_finite() {
+            return Err(ParameterError::BadParameter(
+                "mean_num_mutations must be finite".to_string(),
+            ));
+        }
+        if mean_num_mutations < 0.0 {
+            return Err(ParameterError::BadParameter(
+                "mean_num_mutations must be >= 0".to_string(),
+            ));
+        }
+        Ok(Self {
+            mean_num_mutations,
+            mutation_model,
+            ..self
+        })
+    }
+}
+
+/// How a simulation was run.
+#[derive(Copy, Clone)]
+pub struct Provenance {
+    pub seed: u64,
+    pub parameters: Parameters,
+}
+
+impl Provenance {
+    /// A JSON record following the tskit provenance
+    /// schema, for the software `name` at `version`.
+    pub fn to_json(&self, name: &str, version: &str) -> String {
+        let p = &self.parameters;
+        format!(
+            concat!(
+                r#"{{"schema_version":"1.0.0","#,
+                r#""software":{{"name":"{}","version":"{}"}},"#,
+                r#""parameters":{{"seed":{},"death_probability":{},"mean_num_crossovers":{},"#,
+                r#""nsteps":{},"mean_num_mutations":{},"mutation_model":"{:?}"}},"#,
+                r#""environment":{{"os":{{"system":"{}"}}}}}}"#
+            ),
+            name,
+            version,
+            self.seed,
+            p.death_probability,
+            p.mean_num_crossovers,
+            p.nsteps,
+            p.mean_num_mutations,
+            p.mutation_model,
+            std::env::consts::OS,
+        )
+    }
 }
 
 fn fill_transmissions(
@@ -172,6 +315,28 @@ fn generate_crossover_positions(
     crossovers.push(genome_length);
 }
 
+fn generate_mutations(
+    genome_length: LargeSignedInteger,
+    num_mutations: u64,
+    mutation_model: MutationModel,
+    rng: &mut rand_pcg::Pcg64,
+    mutations: &mut Vec<Mutation>,
+) {
+    mutations.clear();
+    for _ in 0..num_mutations {
+        let position = match mutation_model {
+            MutationModel::InfiniteSites => {
+                rand_distr::Uniform::new(0.0, genome_length as f64).sample(rng)
+            }
+            MutationModel::FiniteSites => {
+                rand_distr::Uniform::new(0, genome_length).sample(rng) as f64
+            }
+        };
+        mutations.push(Mutation { position });
+    }
+    mutations.sort_unstable_by(|a, b| a.position.total_cmp(&b.position));
+}
+
 fn make_crossover_position_distribution(
     genome_length: LargeSignedInteger,
 ) -> rand_distr::Uniform<LargeSignedInteger> {
@@ -184,48 +349,133 @@ pub fn evolve<N: EvolveAncestry>(
     parameters: Parameters,
     population: &mut N,
 ) -> Result<(), Box<dyn Error>> {
+    ancestry_common::phase!("evolve");
     let rng = Rc::new(RefCell::<rand_pcg::Pcg64>::new(
         rand_pcg::Pcg64::seed_from_u64(seed),
     ));
     let mut death = Death::new(parameters.death_probability, rng.clone());
 
+    population.record_provenance(&Provenance { seed, parameters })?;
     population.setup(parameters.nsteps);
 
     let popsize = population.current_population_size();
 
     let parent_picker = rand_distr::Uniform::new(0, popsize);
-    let num_crossovers = rand_distr::Poisson::new(parameters.mean_num_crossovers)?;
+    // rand_distr::Poisson requires a mean > 0, and this keeps
+    // simulations without mutations on the same random number stream.
+    let num_mutations = if parameters.mean_num_mutations > 0.0 {
+        Some(rand_distr::Poisson::new(parameters.mean_num_mutations)?)
+    } else {
+        None
+    };
     let crossover_position = make_crossover_position_distribution(population.genome_length());
     let mendel = rand_distr::Bernoulli::new(0.5).unwrap();
     let mut transmissions: Vec<TransmittedSegment> = vec![];
     let mut crossovers: Vec<LargeSignedInteger> = vec![];
+    let mut mutations: Vec<Mutation> = vec![];
     for step in 1..parameters.nsteps + 1 {
-        let nreplacements = population.generate_deaths(&mut death);
+        let nreplacements = {
+            ancestry_common::phase!("generate_deaths");
+            population.generate_deaths(&mut death)
+        };
         let mut mut_borrowed_rng = rng.borrow_mut();
         for _ in 0..nreplacements {
-            let mut p1 = parent_picker.sample(mut_borrowed_rng.deref_mut());
-            let mut p2 = parent_picker.sample(mut_borrowed_rng.deref_mut());
-            if mendel.sample(mut_borrowed_rng.deref_mut()) {
-                std::mem::swap(&mut p1, &mut p2);
+            {
+                ancestry_common::phase!("generate_transmissions");
+                let mut p1 = parent_picker.sample(mut_borrowed_rng.deref_mut());
+                let mut p2 = parent_picker.sample(mut_borrowed_rng.deref_mut());
+                if mendel.sample(mut_borrowed_rng.deref_mut()) {
+                    std::mem::swap(&mut p1, &mut p2);
+                }
+                let n = 1;
+                generate_crossover_positions(
+                    population.genome_length(),
+                    n,
+                    &crossover_position,
+                    mut_borrowed_rng.deref_mut(),
+                    &mut crossovers,
+                );
+                fill_transmissions(p1, p2, &crossovers, &mut transmissions);
+            }
+            {
+                ancestry_common::phase!("record_birth");
+                population.record_birth(step, parameters.nsteps, &transmissions)?;
+            }
+            if let Some(poisson) = &num_mutations {
+                {
+                    ancestry_common::phase!("generate_mutations");
+                    let n = poisson.sample(mut_borrowed_rng.deref_mut()) as u64;
+                    generate_mutations(
+                        population.genome_length(),
+                        n,
+                        parameters.mutation_model,
+                        mut_borrowed_rng.deref_mut(),
+                        &mut mutations,
+                    );
+                }
+                ancestry_common::phase!("record_mutations");
+                population.record_mutations(step, parameters.nsteps, &mutations)?;
             }
-            // let n = num_crossovers.sample(mut_borrowed_rng.deref_mut()) as u64;
-            let n = 1;
-            generate_crossover_positions(
-                population.genome_length(),
-                n,
-                &crossover_position,
-                mut_borrowed_rng.deref_mut(),
-                &mut crossovers,
-            );
-            fill_transmissions(p1, p2, &crossovers, &mut transmissions);
-            population.record_birth(step, parameters.nsteps, &transmissions)?;
         }
+        ancestry_common::phase!("simplify");
         population.simplify(step)?;
     }
 
+    ancestry_common::phase!("finish");
     population.finish(parameters.nsteps)
 }
 
+/// Replay an explicit pedigree instead of simulating one.
+///
+/// `generations[i]` holds the births at time step `i + 1`,
+/// ordered by the index of the individual that each one replaces.
+///
+/// # Errors
+///
+/// [ParameterError] if there are no generations, if births are not
+/// ordered by replacement index, or if any index is out of range.
+/// Otherwise, any error from `population`.
+pub fn replay<N: EvolveAncestry>(
+    generations: &[Vec<ScriptedBirth>],
+    population: &mut N,
+) -> Result<(), Box<dyn Error>> {
+    if generations.is_empty() {
+        return Err(Box::new(ParameterError::BadParameter(
+            "there must be at least one generation".to_string(),
+        )));
+    }
+    let nsteps = generations.len() as LargeSignedInteger;
+
+    population.setup(nsteps);
+
+    let popsize = population.current_population_size();
+
+    for (step, births) in (1..nsteps + 1).zip(generations.iter()) {
+        if !births.windows(2).all(|w| w[0].replaces < w[1].replaces) {
+            return Err(Box::new(ParameterError::BadParameter(
+                "births must be sorted by unique replacement index".to_string(),
+            )));
+        }
+        let mut fates = vec![false; popsize];
+        for b in births {
+            if b.replaces >= popsize || b.transmissions.iter().any(|t| t.parent >= popsize) {
+                return Err(Box::new(ParameterError::BadParameter(
+                    "individual index out of range".to_string(),
+                )));
+            }
+            fates[b.replaces] = true;
+        }
+        let nreplacements = population.generate_deaths(&mut Death::new_scripted(fates));
+        assert_eq!(nreplacements, births.len());
+        for b in births {
+            population.record_birth(step, nsteps, &b.transmissions)?;
+        }
+        population.simplify(step)?;
+    }
+
+    population.finish(nsteps)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -272,6 +522,56 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_bad_mutation_parameters() {
+        let p = Parameters::new(1.0, 0.0, 10).unwrap();
+        assert!(p
+            .with_mutations(-1.0, MutationModel::InfiniteSites)
+            .is_err());
+        assert!(p
+            .with_mutations(f64::NAN, MutationModel::FiniteSites)
+            .is_err());
+        assert!(p.with_mutations(0.0, MutationModel::FiniteSites).is_ok());
+    }
+
+    #[test]
+    fn test_provenance_json() {
+        let parameters = Parameters::new(0.5, 1e-3, 10)
+            .unwrap()
+            .with_mutations(2.0, MutationModel::FiniteSites)
+            .unwrap();
+        let json = Provenance {
+            seed: 101,
+            parameters,
+        }
+        .to_json("name", "1.2.3");
+        assert!(json.starts_with(r#"{"schema_version":"1.0.0","#));
+        assert!(json.contains(r#""software":{"name":"name","version":"1.2.3"}"#));
+        assert!(json.contains(r#""seed":101,"death_probability":0.5,"mean_num_crossovers":0.001,"#));
+        assert!(
+            json.contains(r#""nsteps":10,"mean_num_mutations":2,"mutation_model":"FiniteSites"}"#)
+        );
+        assert_eq!(json.matches('{').count(), json.matches('}').count());
+    }
+
+    #[test]
+    fn test_generate_mutations() {
+        let mut rng = make_rng(101);
+        let genome_length = 10_i64;
+        let mut mutations = vec![];
+        for model in [MutationModel::InfiniteSites, MutationModel::FiniteSites] {
+            generate_mutations(genome_length, 100, model, &mut rng, &mut mutations);
+            assert_eq!(mutations.len(), 100);
+            assert!(mutations
+                .iter()
+                .all(|m| m.position >= 0.0 && m.position < genome_length as f64));
+            assert!(mutations.windows(2).all(|w| w[0].position <= w[1].position));
+            if model == MutationModel::FiniteSites {
+                assert!(mutations.iter().all(|m| m.position.fract() == 0.0));
+            }
+        }
+    }
+
     #[test]
     fn test_fill_transmissions() {
         let p1 = 0_usize;
@@ -339,4 +639,90 @@ mod tests {
             validate_transmissions!(expected, transmissions);
         }
     }
+
+    // Records who dies and who is born, and nothing else.
+    #[derive(Default)]
+    struct Recorder {
+        replacements: Vec<Vec<usize>>,
+        births: Vec<(LargeSignedInteger, Vec<TransmittedSegment>)>,
+    }
+
+    impl EvolveAncestry for Recorder {
+        fn genome_length(&self) -> LargeSignedInteger {
+            10
+        }
+
+        fn setup(&mut self, _final_time: LargeSignedInteger) {}
+
+        fn generate_deaths(&mut self, death: &mut Death) -> usize {
+            let dead = (0..3).filter(|_| death.dies()).collect::<Vec<_>>();
+            let n = dead.len();
+            self.replacements.push(dead);
+            n
+        }
+
+        fn current_population_size(&self) -> usize {
+            3
+        }
+
+        fn record_birth(
+            &mut self,
+            birth_time: LargeSignedInteger,
+            _final_time: LargeSignedInteger,
+            breakpoints: &[TransmittedSegment],
+        ) -> Result<(), Box<dyn Error>> {
+            self.births.push((birth_time, breakpoints.to_vec()));
+            Ok(())
+        }
+
+        fn simplify(
+            &mut self,
+            _current_time_point: LargeSignedInteger,
+        ) -> Result<(), Box<dyn Error>> {
+            Ok(())
+        }
+
+        fn finish(
+            &mut self,
+            _current_time_point: LargeSignedInteger,
+        ) -> Result<(), Box<dyn Error>> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_replay() {
+        let generations = vec![
+            vec![
+                ScriptedBirth::new(0, vec![make_transmission(0, 10, 1)]),
+                ScriptedBirth::new(
+                    2,
+                    vec![make_transmission(0, 5, 0), make_transmission(5, 10, 1)],
+                ),
+            ],
+            vec![],
+        ];
+        let mut recorder = Recorder::default();
+        replay(&generations, &mut recorder).unwrap();
+        assert_eq!(recorder.replacements, vec![vec![0, 2], vec![]]);
+        assert_eq!(recorder.births.len(), 2);
+        assert_eq!(recorder.births[0].0, 1);
+        assert_eq!(recorder.births[1].1, generations[0][1].transmissions);
+    }
+
+    #[test]
+    fn test_replay_bad_input() {
+        let mut recorder = Recorder::default();
+        assert!(replay(&[], &mut recorder).is_err());
+        let unsorted = vec![vec![
+            ScriptedBirth::new(2, vec![make_transmission(0, 10, 1)]),
+            ScriptedBirth::new(0, vec![make_transmission(0, 10, 1)]),
+        ]];
+        assert!(replay(&unsorted, &mut recorder).is_err());
+        let out_of_range = vec![vec![ScriptedBirth::new(
+            0,
+            vec![make_transmission(0, 10, 3)],
+        )]];
+        assert!(replay(&out_of_range, &mut recorder).is_err());
+    }
 }
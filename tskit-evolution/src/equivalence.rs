@@ -0,0 +1,458 @@
+//! Compare the genealogies stored in two table collections,
+//! independent of how nodes are labelled.
+//!
+//! Samples are matched up by a user-supplied key.
+//! For each local tree, we record every sample and every
+//! node with more than one child as the set of sample keys below it
+//! plus its time. Unary nodes are skipped, so tables need not be
+//! fully simplified.
+//!
+//! Times are compared relative to the youngest sample,
+//! so the two tables may use different time origins.
+//!
+//! Mutations are compared by position, time, and the
+//! sample keys below them.
+
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+use thiserror::Error;
+use tskit::prelude::*;
+use tskit::TableCollection;
+
+#[derive(Error, Debug)]
+pub enum EquivalenceError {
+    #[error("sequence lengths differ: {left:?} vs {right:?}")]
+    SequenceLength { left: f64, right: f64 },
+    #[error("numbers of samples differ: {left:?} vs {right:?}")]
+    NumSamples { left: usize, right: usize },
+    #[error("sample keys differ")]
+    SampleKeys,
+    #[error("local trees differ on [{left:?}, {right:?})")]
+    LocalTree { left: f64, right: f64 },
+    #[error("mutations differ on [{left:?}, {right:?})")]
+    Mutations { left: f64, right: f64 },
+    #[error("{0:?}")]
+    TskitError(tskit::TskitError),
+}
+
+impl From<tskit::TskitError> for EquivalenceError {
+    fn from(e: tskit::TskitError) -> Self {
+        Self::TskitError(e)
+    }
+}
+
+// (node time, sorted sample keys below the node)
+type Clusters<K> = Vec<(f64, Vec<K>)>;
+
+// (position, mutation time, sorted sample keys below the mutation)
+type Mutations<K> = Vec<(f64, f64, Vec<K>)>;
+
+struct KeyedTreeSequence<K> {
+    treeseq: tskit::TreeSequence,
+    sample_keys: HashMap<NodeId, K>,
+    times: Vec<f64>,
+    // (position, time, node), sorted by position
+    mutations: Vec<(f64, f64, NodeId)>,
+}
+
+impl<K: Ord + Hash + Clone> KeyedTreeSequence<K> {
+    fn new<F: Fn(NodeId) -> K>(
+        tables: &TableCollection,
+        sample_key: F,
+    ) -> Result<Self, EquivalenceError> {
+        let mut times = tables
+            .nodes_iter()
+            .map(|n| f64::from(n.time))
+            .collect::<Vec<_>>();
+        let treeseq = tables
+            .deepcopy()?
+            .tree_sequence(tskit::TreeSequenceFlags::BUILD_INDEXES)?;
+        let sample_keys = treeseq
+            .sample_nodes()
+            .iter()
+            .map(|s| (*s, sample_key(*s)))
+            .collect::<HashMap<_, _>>();
+        let youngest = sample_keys
+            .keys()
+            .map(|s| times[usize::from(*s)])
+            .fold(f64::INFINITY, f64::min);
+        for t in times.iter_mut() {
+            *t -= youngest;
+        }
+        let positions = tables
+            .sites_iter()
+            .map(|s| f64::from(s.position))
+            .collect::<Vec<_>>();
+        let mut mutations = tables
+            .mutations_iter()
+            .map(|m| {
+                (
+                    positions[usize::from(m.site)],
+                    f64::from(m.time) - youngest,
+                    m.node,
+                )
+            })
+            .collect::<Vec<_>>();
+        mutations.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Ok(Self {
+            treeseq,
+            sample_keys,
+            times,
+            mutations,
+        })
+    }
+
+    fn sorted_keys(&self) -> Vec<K> {
+        let mut keys = self.sample_keys.values().cloned().collect::<Vec<_>>();
+        keys.sort();
+        keys
+    }
+
+    fn local_tree(&self, tree: &tskit::Tree) -> (Clusters<K>, Mutations<K>) {
+        let parents = tree.parent_array();
+        let mut reachable = vec![];
+        let mut visited = HashSet::new();
+        for s in self.sample_keys.keys() {
+            let mut u = *s;
+            while visited.insert(u) {
+                reachable.push(u);
+                let p = parents[usize::from(u)];
+                if p.is_null() {
+                    break;
+                }
+                u = p;
+            }
+        }
+
+        let mut num_children = HashMap::<NodeId, usize>::new();
+        for u in reachable.iter() {
+            let p = parents[usize::from(*u)];
+            if !p.is_null() {
+                *num_children.entry(p).or_default() += 1;
+            }
+        }
+
+        // Children are younger than parents, so this is a postorder.
+        reachable.sort_by(|a, b| {
+            self.times[usize::from(*a)]
+                .partial_cmp(&self.times[usize::from(*b)])
+                .unwrap()
+                .then(a.cmp(b))
+        });
+
+        let (left, right) = tree.interval();
+        let (left, right) = (f64::from(left), f64::from(right));
+        let first = self.mutations.partition_point(|m| m.0 < left);
+        let mut mutations_on = HashMap::<NodeId, Vec<(f64, f64)>>::new();
+        for m in self.mutations[first..].iter().take_while(|m| m.0 < right) {
+            mutations_on.entry(m.2).or_default().push((m.0, m.1));
+        }
+
+        let mut below = HashMap::<NodeId, Vec<K>>::new();
+        for (s, k) in self.sample_keys.iter() {
+            below.insert(*s, vec![k.clone()]);
+        }
+
+        let mut clusters = vec![];
+        let mut mutations = vec![];
+        for u in reachable {
+            let mut keys = below.remove(&u).unwrap_or_default();
+            keys.sort();
+            if self.sample_keys.contains_key(&u) || num_children.get(&u).copied().unwrap_or(0) > 1 {
+                clusters.push((self.times[usize::from(u)], keys.clone()));
+            }
+            for (position, time) in mutations_on.remove(&u).unwrap_or_default() {
+                mutations.push((position, time, keys.clone()));
+            }
+            let p = parents[usize::from(u)];
+            if !p.is_null() {
+                below.entry(p).or_default().extend(keys);
+            }
+        }
+        // Mutations on nodes that are not ancestral to any sample
+        for (_, m) in mutations_on {
+            for (position, time) in m {
+                mutations.push((position, time, vec![]));
+            }
+        }
+        clusters.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap().then_with(|| a.1.cmp(&b.1)));
+        mutations.sort_by(|a, b| {
+            a.0.total_cmp(&b.0)
+                .then(a.1.total_cmp(&b.1))
+                .then_with(|| a.2.cmp(&b.2))
+        });
+        (clusters, mutations)
+    }
+}
+
+/// Check that two table collections contain the same genealogies
+/// and mutations.
+///
+/// `key_a` and `key_b` map the sample nodes of `a` and `b`,
+/// respectively, to keys that identify the same sample in both.
+///
+/// Local trees are compared by their clusters, which only
+/// include samples and nodes with more than one child.
+/// Differences in unary nodes, such as those kept by
+/// [KEEP_UNARY](tskit::SimplificationOptions::KEEP_UNARY),
+/// are not detected. The trees need not have the same
+/// breakpoints, only the same clusters at every position.
+///
+/// # Errors
+///
+/// Returns the first difference found, or [TskitError](EquivalenceError::TskitError)
+/// if either table collection cannot be turned into a tree sequence.
+pub fn compare_genealogies<K, FA, FB>(
+    a: &TableCollection,
+    b: &TableCollection,
+    key_a: FA,
+    key_b: FB,
+) -> Result<(), EquivalenceError>
+where
+    K: Ord + Hash + Clone,
+    FA: Fn(NodeId) -> K,
+    FB: Fn(NodeId) -> K,
+{
+    let length_a = f64::from(a.sequence_length());
+    let length_b = f64::from(b.sequence_length());
+    if length_a != length_b {
+        return Err(EquivalenceError::SequenceLength {
+            left: length_a,
+            right: length_b,
+        });
+    }
+
+    let keyed_a = KeyedTreeSequence::new(a, key_a)?;
+    let keyed_b = KeyedTreeSequence::new(b, key_b)?;
+    if keyed_a.sample_keys.len() != keyed_b.sample_keys.len() {
+        return Err(EquivalenceError::NumSamples {
+            left: keyed_a.sample_keys.len(),
+            right: keyed_b.sample_keys.len(),
+        });
+    }
+    if keyed_a.sorted_keys() != keyed_b.sorted_keys() {
+        return Err(EquivalenceError::SampleKeys);
+    }
+
+    let mut trees_a = keyed_a.treeseq.tree_iterator(tskit::TreeFlags::default())?;
+    let mut trees_b = keyed_b.treeseq.tree_iterator(tskit::TreeFlags::default())?;
+
+    // Walk both sets of trees from left to right,
+    // advancing whichever one ends first.
+    let mut left = 0.0;
+    let mut right_a = 0.0;
+    let mut right_b = 0.0;
+    let mut local_a = (vec![], vec![]);
+    let mut local_b = (vec![], vec![]);
+    while left < length_a {
+        // Running out of trees before the end of the
+        // sequence is reported as a difference from there on.
+        if right_a <= left {
+            let tree = trees_a.next().ok_or(EquivalenceError::LocalTree {
+                left,
+                right: length_a,
+            })?;
+            right_a = f64::from(tree.interval().1);
+            local_a = keyed_a.local_tree(tree);
+        }
+        if right_b <= left {
+            let tree = trees_b.next().ok_or(EquivalenceError::LocalTree {
+                left,
+                right: length_a,
+            })?;
+            right_b = f64::from(tree.interval().1);
+            local_b = keyed_b.local_tree(tree);
+        }
+        let right = f64::min(right_a, right_b);
+        if local_a.0 != local_b.0 {
+            return Err(EquivalenceError::LocalTree { left, right });
+        }
+        let in_interval = |m: &&(f64, f64, Vec<K>)| m.0 >= left && m.0 < right;
+        if !local_a
+            .1
+            .iter()
+            .filter(in_interval)
+            .eq(local_b.1.iter().filter(in_interval))
+        {
+            return Err(EquivalenceError::Mutations { left, right });
+        }
+        left = right;
+    }
+
+    Ok(())
+}
+
+/// Check that two table collections contain the same genealogies,
+/// matching the i-th sample node of `a` to the i-th sample node of `b`.
+///
+/// Both backends output their alive nodes as samples
+/// in the order that they occur in the population,
+/// making this the natural comparison between them.
+pub fn compare_genealogies_by_sample_order(
+    a: &TableCollection,
+    b: &TableCollection,
+) -> Result<(), EquivalenceError> {
+    let rank = |tables: &TableCollection| {
+        tables
+            .nodes_iter()
+            .filter(|n| n.flags.is_sample())
+            .enumerate()
+            .map(|(i, n)| (n.id, i))
+            .collect::<HashMap<_, _>>()
+    };
+    let rank_a = rank(a);
+    let rank_b = rank(b);
+    compare_genealogies(a, b, |s| rank_a[&s], |s| rank_b[&s])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Two samples and their parent.
+    // The parent is either the first or the last node.
+    fn cherry(parent_first: bool, parent_time: f64) -> TableCollection {
+        let mut tables = TableCollection::new(10.).unwrap();
+        let add_parent = |tables: &mut TableCollection| {
+            tables
+                .add_node(0, parent_time, PopulationId::NULL, IndividualId::NULL)
+                .unwrap()
+        };
+        let mut parent = NodeId::NULL;
+        if parent_first {
+            parent = add_parent(&mut tables);
+        }
+        let mut samples = vec![];
+        for _ in 0..2 {
+            samples.push(
+                tables
+                    .add_node(
+                        tskit::NodeFlags::IS_SAMPLE,
+                        0.0,
+                        PopulationId::NULL,
+                        IndividualId::NULL,
+                    )
+                    .unwrap(),
+            );
+        }
+        if !parent_first {
+            parent = add_parent(&mut tables);
+        }
+        for s in samples {
+            tables.add_edge(0., 10., parent, s).unwrap();
+        }
+        tables
+            .full_sort(tskit::TableSortOptions::default())
+            .unwrap();
+        tables
+    }
+
+    #[test]
+    fn test_relabelled_tables_are_equivalent() {
+        let a = cherry(true, 1.0);
+        let b = cherry(false, 1.0);
+        assert!(compare_genealogies_by_sample_order(&a, &b).is_ok());
+    }
+
+    // As cherry(true, 1.0), but with edges split at 5, giving two
+    // trees. The second tree has a parent of the given time.
+    fn two_cherries(second_parent_time: f64) -> TableCollection {
+        let mut tables = TableCollection::new(10.).unwrap();
+        let first = tables
+            .add_node(0, 1.0, PopulationId::NULL, IndividualId::NULL)
+            .unwrap();
+        let second = if second_parent_time == 1.0 {
+            first
+        } else {
+            tables
+                .add_node(
+                    0,
+                    second_parent_time,
+                    PopulationId::NULL,
+                    IndividualId::NULL,
+                )
+                .unwrap()
+        };
+        for _ in 0..2 {
+            let sample = tables
+                .add_node(
+                    tskit::NodeFlags::IS_SAMPLE,
+                    0.0,
+                    PopulationId::NULL,
+                    IndividualId::NULL,
+                )
+                .unwrap();
+            tables.add_edge(0., 5., first, sample).unwrap();
+            tables.add_edge(5., 10., second, sample).unwrap();
+        }
+        tables
+            .full_sort(tskit::TableSortOptions::default())
+            .unwrap();
+        tables
+    }
+
+    #[test]
+    fn test_numbers_of_trees_differ() {
+        let a = cherry(true, 1.0);
+        assert!(compare_genealogies_by_sample_order(&a, &two_cherries(1.0)).is_ok());
+        assert!(compare_genealogies_by_sample_order(&two_cherries(1.0), &a).is_ok());
+        assert!(matches!(
+            compare_genealogies_by_sample_order(&a, &two_cherries(2.0)),
+            Err(EquivalenceError::LocalTree { left, right }) if left == 5.0 && right == 10.0
+        ));
+    }
+
+    #[test]
+    fn test_branch_lengths_differ() {
+        let a = cherry(true, 1.0);
+        let b = cherry(true, 2.0);
+        assert!(matches!(
+            compare_genealogies_by_sample_order(&a, &b),
+            Err(EquivalenceError::LocalTree { .. })
+        ));
+    }
+
+    // Add a mutation above the first sample.
+    fn add_mutation(tables: &mut TableCollection, position: f64) {
+        let sample = tables
+            .nodes_iter()
+            .find(|n| n.flags.is_sample())
+            .unwrap()
+            .id;
+        let site = tables.add_site(position, Some(b"0")).unwrap();
+        tables
+            .add_mutation(site, sample, MutationId::NULL, 0.0, Some(b"1"))
+            .unwrap();
+    }
+
+    #[test]
+    fn test_identical_mutations() {
+        let mut a = cherry(true, 1.0);
+        let mut b = cherry(false, 1.0);
+        add_mutation(&mut a, 2.5);
+        add_mutation(&mut b, 2.5);
+        assert!(compare_genealogies_by_sample_order(&a, &b).is_ok());
+    }
+
+    #[test]
+    fn test_mutations_differ() {
+        let mut a = cherry(true, 1.0);
+        let mut b = cherry(true, 1.0);
+        add_mutation(&mut a, 2.5);
+        add_mutation(&mut b, 3.5);
+        assert!(matches!(
+            compare_genealogies_by_sample_order(&a, &b),
+            Err(EquivalenceError::Mutations { .. })
+        ));
+    }
+
+    #[test]
+    fn test_sample_keys_differ() {
+        let a = cherry(true, 1.0);
+        let b = cherry(true, 1.0);
+        assert!(matches!(
+            compare_genealogies(&a, &b, |_| 0, |_| 1),
+            Err(EquivalenceError::SampleKeys)
+        ));
+    }
+}
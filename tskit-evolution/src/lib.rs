@@ -1,42 +1,368 @@
 use ancestry_common::{LargeSignedInteger, SignedInteger};
 use neutral_evolution::EvolveAncestry;
 use tskit::prelude::*;
+use tskit::provenance::Provenance;
 use tskit::TableCollection;
 
+mod equivalence;
+mod mutations;
+mod simplification_hook;
+mod simplification_policy;
+mod vcf;
+
+pub use equivalence::{compare_genealogies, compare_genealogies_by_sample_order, EquivalenceError};
+pub use mutations::{place_mutations, MutationError, RateMap};
+pub use simplification_hook::SimplificationHook;
+pub use simplification_policy::{SimplificationPolicy, SimplificationPolicyError};
+pub use vcf::{write_vcf, VcfError};
+
+/// How the edges of births are made ready for simplification.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EdgeStrategy {
+    /// Append edges to the edge table and sort
+    /// those added since the last simplification.
+    ///
+    /// The new edges are then moved in front of the existing ones,
+    /// which is only a valid order if no parent of a new edge
+    /// already has edges in the table. That is the case when
+    /// generations do not overlap (death probability 1).
+    /// Otherwise, alive parents keep their edges and the new ones must be
+    /// interleaved, so all edges are sorted instead. With overlapping
+    /// generations this happens at almost every simplification; see
+    /// [num_full_sort_fallbacks](EvolvableTableCollection::num_full_sort_fallbacks).
+    IncrementalSort,
+    /// Append edges to the edge table and sort all of them.
+    FullSort,
+    /// Buffer edges by parent and merge them into the
+    /// (already sorted) edge table, so that no edges are sorted.
+    ///
+    /// The merge still reads the parent of every existing edge and
+    /// moves those that sort after new edges (with non-overlapping
+    /// generations, all of them), so each simplification costs time
+    /// linear in the number of edges. New sites and mutations are
+    /// sorted as usual.
+    Buffer,
+}
+
 pub struct EvolvableTableCollection {
     tables: TableCollection,
     alive_nodes: Vec<NodeId>,
+    // The current ID of every node ever added, in the
+    // order added, or NULL if simplified away.
     idmap: Vec<NodeId>,
+    // The indexes of the non-NULL entries of idmap.
+    mapped_nodes: Vec<usize>,
+    simplification_hook: Option<Box<dyn SimplificationHook>>,
+    simplification_options: tskit::SimplificationOptions,
+    // Samples in every simplification, in addition to the alive nodes.
+    preserved_nodes: Vec<NodeId>,
     popsize: SignedInteger,
+    // All nodes are in this population.
+    population: PopulationId,
     replacements: Vec<usize>,
     births: Vec<NodeId>,
     bookmark: tskit::types::Bookmark,
-    simplification_interval: LargeSignedInteger,
+    simplification_policy: SimplificationPolicy,
+    adaptive_interval: Option<simplification_policy::AdaptiveInterval>,
     last_time_simplified: Option<LargeSignedInteger>, // TODO: do we really need this?
+    edge_strategy: EdgeStrategy,
+    // How often EdgeStrategy::IncrementalSort had to sort everything.
+    num_full_sort_fallbacks: usize,
+    // For EdgeStrategy::Buffer, the (left, right, child)
+    // segments of each parent, indexed by parent.
+    edge_buffer: Vec<Vec<(Position, Position, NodeId)>>,
+    buffered_parents: Vec<NodeId>,
+    num_buffered_edges: usize,
+    // The mutations left by the last simplification, which are sorted.
+    num_sorted_mutations: u64,
+    // Site IDs by position, so that recurrent mutations share a site.
+    sites: std::collections::HashMap<u64, SiteId>,
 }
 
 impl EvolvableTableCollection {
     pub fn new(
         sequence_length: LargeSignedInteger,
         popsize: SignedInteger,
-        simplification_interval: LargeSignedInteger,
+        simplification_policy: SimplificationPolicy,
+        edge_strategy: EdgeStrategy,
     ) -> Result<Self, Box<dyn std::error::Error>> {
+        simplification_policy.validate()?;
+        let adaptive_interval = match simplification_policy {
+            SimplificationPolicy::Adaptive {
+                initial_interval,
+                max_interval,
+            } => Some(simplification_policy::AdaptiveInterval::new(
+                initial_interval,
+                max_interval,
+            )),
+            _ => None,
+        };
         let mut tables = TableCollection::new(tskit::Position::from(sequence_length as f64))?;
         let mut alive_nodes = vec![];
+        let population = tables.add_population()?;
 
         Ok(Self {
             tables,
             alive_nodes,
             idmap: vec![],
+            mapped_nodes: vec![],
+            simplification_hook: None,
+            simplification_options: tskit::SimplificationOptions::FILTER_INDIVIDUALS,
+            preserved_nodes: vec![],
             popsize,
+            population,
             replacements: vec![],
             births: vec![],
             bookmark: tskit::types::Bookmark::new(),
-            simplification_interval,
+            simplification_policy,
+            adaptive_interval,
             last_time_simplified: None,
+            edge_strategy,
+            num_full_sort_fallbacks: 0,
+            edge_buffer: vec![],
+            buffered_parents: vec![],
+            num_buffered_edges: 0,
+            num_sorted_mutations: 0,
+            sites: std::collections::HashMap::default(),
         })
     }
 
+    /// The current ID of every node ever added, or [NodeId::NULL]
+    /// if it has been simplified away.
+    ///
+    /// Indexed by the order in which nodes were added:
+    /// the founders come first, then each birth.
+    pub fn node_id_map(&self) -> &[NodeId] {
+        &self.idmap
+    }
+
+    /// The number of simplifications for which sorting only the
+    /// new edges did not give a valid order, so that all edges
+    /// were sorted instead.
+    ///
+    /// Always zero unless using [EdgeStrategy::IncrementalSort].
+    pub fn num_full_sort_fallbacks(&self) -> usize {
+        self.num_full_sort_fallbacks
+    }
+
+    /// Call `hook` after each simplification,
+    /// replacing any previous hook.
+    pub fn set_simplification_hook(&mut self, hook: Box<dyn SimplificationHook>) {
+        self.simplification_hook = Some(hook);
+    }
+
+    /// Stop calling the simplification hook, returning it.
+    pub fn take_simplification_hook(&mut self) -> Option<Box<dyn SimplificationHook>> {
+        self.simplification_hook.take()
+    }
+
+    /// Use `options` for all later simplifications.
+    ///
+    /// The default is
+    /// [FILTER_INDIVIDUALS](tskit::SimplificationOptions::FILTER_INDIVIDUALS).
+    /// Every birth adds an individual, so leaving it out of `options`
+    /// keeps the individual of every node ever born.
+    pub fn set_simplification_options(&mut self, options: tskit::SimplificationOptions) {
+        self.simplification_options = options;
+    }
+
+    /// The IDs of the alive nodes as of the end of the last time step.
+    pub fn alive_nodes(&self) -> &[NodeId] {
+        &self.alive_nodes
+    }
+
+    /// Keep `nodes` as samples in all later simplifications,
+    /// and mark them as samples when the simulation finishes.
+    ///
+    /// # Errors
+    ///
+    /// [TskitError::IndexError](tskit::TskitError::IndexError)
+    /// if a node is not in the node table.
+    pub fn preserve_nodes(&mut self, nodes: &[NodeId]) -> Result<(), tskit::TskitError> {
+        let num_nodes = u64::from(self.tables.nodes().num_rows());
+        if nodes
+            .iter()
+            .any(|n| i32::from(*n) < 0 || i32::from(*n) as u64 >= num_nodes)
+        {
+            return Err(tskit::TskitError::IndexError);
+        }
+        for node in nodes {
+            if !self.preserved_nodes.contains(node) {
+                self.preserved_nodes.push(*node);
+            }
+        }
+        Ok(())
+    }
+
+    /// The current IDs of the nodes passed to
+    /// [preserve_nodes](EvolvableTableCollection::preserve_nodes).
+    pub fn preserved_nodes(&self) -> &[NodeId] {
+        &self.preserved_nodes
+    }
+
+    // Each node is a (haploid) individual, whose
+    // parents are the individuals of its parent nodes.
+    fn add_node(
+        &mut self,
+        time: Time,
+        parents: &[IndividualId],
+    ) -> Result<NodeId, tskit::TskitError> {
+        let individual = self.tables.add_individual(0, &[] as &[f64], parents)?;
+        let id = self.tables.add_node(0, time, self.population, individual)?;
+        self.mapped_nodes.push(self.idmap.len());
+        self.idmap.push(id);
+        Ok(id)
+    }
+
+    // The edges left by simplification are sorted, and the
+    // parents of new edges are alive so are (usually) younger
+    // than the parents of existing edges. So we only sort the new
+    // edges and then move them to the front.
+    //
+    // With overlapping generations, an alive parent may also be
+    // a parent in existing edges, in which case its edges are
+    // not contiguous and we fall back to sorting everything.
+    fn sort_edges(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        ancestry_common::phase!("sort_edges");
+        if self.edge_strategy == EdgeStrategy::FullSort || self.bookmark.offsets.edges == 0 {
+            self.tables.full_sort(tskit::TableSortOptions::default())?;
+            return Ok(());
+        }
+
+        self.tables
+            .sort(&self.bookmark, tskit::TableSortOptions::default())?;
+
+        let num_edges = usize::try_from(u64::from(self.tables.edges().num_rows()))?;
+        let offset = usize::try_from(self.bookmark.offsets.edges)?;
+
+        // Get the raw pointer to the tsk_table_collection_t
+        let table_ptr = self.tables.as_mut_ptr();
+
+        // SAFETY: the tskit::TableCollection does not
+        // allow the managed pointer to be NULL, and
+        // each column has num_edges elements.
+        unsafe {
+            // For each column (that we are using), put the newest edges at the front.
+            rotate_left((*table_ptr).edges.parent, num_edges, offset);
+            rotate_left((*table_ptr).edges.child, num_edges, offset);
+            rotate_left((*table_ptr).edges.left, num_edges, offset);
+            rotate_left((*table_ptr).edges.right, num_edges, offset);
+        }
+
+        // This is the only check: simplification checks the
+        // ordering again itself.
+        if self
+            .tables
+            .check_integrity(tskit::TableIntegrityCheckFlags::CHECK_EDGE_ORDERING)
+            .is_err()
+        {
+            self.num_full_sort_fallbacks += 1;
+            self.tables.full_sort(tskit::TableSortOptions::default())?;
+        }
+        Ok(())
+    }
+
+    fn buffer_edge(&mut self, left: Position, right: Position, parent: NodeId, child: NodeId) {
+        let p = usize::from(parent);
+        if p >= self.edge_buffer.len() {
+            self.edge_buffer.resize(p + 1, vec![]);
+        }
+        if self.edge_buffer[p].is_empty() {
+            self.buffered_parents.push(parent);
+        }
+        self.edge_buffer[p].push((left, right, child));
+        self.num_buffered_edges += 1;
+    }
+
+    // The edge table is sorted by parent time, and so are the
+    // buffered parents once sorted, so merging the two by time
+    // gives a sorted table. Alive parents may have edges in both
+    // (with overlapping generations); these are moved into the
+    // buffer first, so that each parent's edges are contiguous.
+    //
+    // The merge is done in place in the edge columns, from the back,
+    // so that no row is overwritten before it has been moved.
+    fn flush_edge_buffer(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        ancestry_common::phase!("flush_edge_buffer");
+        let num_existing = usize::try_from(u64::from(self.tables.edges().num_rows()))?;
+
+        // Make room for the buffered edges, which the merge overwrites.
+        for parent in self.buffered_parents.iter() {
+            for (left, right, child) in self.edge_buffer[usize::from(*parent)].iter() {
+                self.tables.add_edge(*left, *right, *parent, *child)?;
+            }
+        }
+        let num_edges = num_existing + self.num_buffered_edges;
+
+        let table_ptr = self.tables.as_mut_ptr();
+        // SAFETY: the tskit::TableCollection does not
+        // allow the managed pointer to be NULL, and
+        // each column has num_edges elements.
+        let (left, right, parent, child) = unsafe {
+            (
+                std::slice::from_raw_parts_mut((*table_ptr).edges.left, num_edges),
+                std::slice::from_raw_parts_mut((*table_ptr).edges.right, num_edges),
+                std::slice::from_raw_parts_mut((*table_ptr).edges.parent, num_edges),
+                std::slice::from_raw_parts_mut((*table_ptr).edges.child, num_edges),
+            )
+        };
+
+        let mut kept = 0;
+        for i in 0..num_existing {
+            match self.edge_buffer.get_mut(usize::try_from(parent[i])?) {
+                Some(buffer) if !buffer.is_empty() => {
+                    buffer.push((left[i].into(), right[i].into(), child[i].into()))
+                }
+                _ => {
+                    left[kept] = left[i];
+                    right[kept] = right[i];
+                    parent[kept] = parent[i];
+                    child[kept] = child[i];
+                    kept += 1;
+                }
+            }
+        }
+
+        let nodes = self.tables.nodes();
+        let time = |parent: NodeId| nodes.time(parent).unwrap();
+        self.buffered_parents
+            .sort_unstable_by(|a, b| (time(*a), *a).partial_cmp(&(time(*b), *b)).unwrap());
+
+        let mut next = num_edges;
+        for p in self.buffered_parents.iter().rev() {
+            while kept > 0 && time(parent[kept - 1].into()) > time(*p) {
+                kept -= 1;
+                next -= 1;
+                left[next] = left[kept];
+                right[next] = right[kept];
+                parent[next] = parent[kept];
+                child[next] = child[kept];
+            }
+            let mut children = std::mem::take(&mut self.edge_buffer[usize::from(*p)]);
+            children.sort_unstable_by(|a, b| (a.2, a.0).partial_cmp(&(b.2, b.0)).unwrap());
+            for (l, r, c) in children.into_iter().rev() {
+                next -= 1;
+                left[next] = l.into();
+                right[next] = r.into();
+                parent[next] = (*p).into();
+                child[next] = c.into();
+            }
+        }
+        debug_assert_eq!(next, kept);
+        self.buffered_parents.clear();
+        self.num_buffered_edges = 0;
+
+        // Sites and mutations are still appended as they arise,
+        // so sort them (but not the edges) if there are new ones.
+        if u64::from(self.tables.mutations().num_rows()) > self.num_sorted_mutations {
+            let mut bookmark = tskit::types::Bookmark::new();
+            bookmark.offsets.edges = u64::from(self.tables.edges().num_rows());
+            self.tables
+                .sort(&bookmark, tskit::TableSortOptions::default())?;
+        }
+        Ok(())
+    }
+
     fn enact_replacements(&mut self) {
         if !self.births.is_empty() {
             assert_eq!(self.replacements.len(), self.births.len());
@@ -48,57 +374,87 @@ impl EvolvableTableCollection {
         self.births.clear();
     }
 
+    // The size of the columns that we use.
+    fn estimated_bytes(&self) -> usize {
+        let rows = |n: tskit::SizeType| u64::from(n) as usize;
+        // time, flags, population, individual, metadata offset
+        rows(self.tables.nodes().num_rows()) * 28
+            // left, right, parent, child, metadata offset
+            + rows(self.tables.edges().num_rows()) * 32
+            // position, ancestral state and its offset, metadata offset
+            + rows(self.tables.sites().num_rows()) * 25
+            // site, node, parent, time, derived state and its offset, metadata offset
+            + rows(self.tables.mutations().num_rows()) * 37
+            // flags, location and parents offsets, (two) parents, metadata offset
+            + rows(self.tables.individuals().num_rows()) * 36
+            + self.num_buffered_edges * std::mem::size_of::<(Position, Position, NodeId)>()
+    }
+
+    fn simplification_due(&self, current_time_point: LargeSignedInteger) -> bool {
+        let since_last = current_time_point - self.last_time_simplified.unwrap_or(0);
+        match self.simplification_policy {
+            SimplificationPolicy::Interval(n) => current_time_point % n == 0,
+            SimplificationPolicy::EdgeRows(n) => {
+                u64::from(self.tables.edges().num_rows()) + self.num_buffered_edges as u64 >= n
+            }
+            SimplificationPolicy::MemoryBudget(bytes) => self.estimated_bytes() >= bytes,
+            SimplificationPolicy::Adaptive { .. } => {
+                since_last >= self.adaptive_interval.as_ref().unwrap().interval
+            }
+        }
+    }
+
     fn simplify_details(
         &mut self,
         current_time_point: LargeSignedInteger,
         force: bool,
     ) -> Result<(), Box<dyn std::error::Error>> {
         self.enact_replacements();
-        if current_time_point > 0
-            && (force || current_time_point % self.simplification_interval == 0)
-        {
-            self.tables.full_sort(tskit::TableSortOptions::default())?;
+        if current_time_point > 0 && (force || self.simplification_due(current_time_point)) {
+            let start = std::time::Instant::now();
+            let since_last = current_time_point - self.last_time_simplified.unwrap_or(0);
+            // The bookmark holds the edge rows kept by the last simplification.
+            let edge_rows_kept = self.bookmark.offsets.edges;
+            let edge_rows_added = u64::from(self.tables.edges().num_rows())
+                + self.num_buffered_edges as u64
+                - edge_rows_kept;
 
-            self.tables
-                .check_integrity(tskit::TableIntegrityCheckFlags::CHECK_EDGE_ORDERING)?;
-
-            // if self.bookmark.offsets.edges > 0 {
-            //     // To simplify, the edge table must
-            //     // have the newest edges at the front.
-            //     // Sorting using a bookmark defines where
-            //     // to start sorting FROM.  So, we need to rotate
-            //     // each column
-
-            //     let num_edges = usize::from(self.tables.edges().num_rows());
-
-            //     // Get the raw pointer to the tsk_table_collection_t
-            //     let table_ptr = self.tables.as_mut_ptr();
-
-            //     let offset = usize::try_from(self.bookmark.offsets.edges)?;
-
-            //     // SAFETY: the tskit::TableCollection does not
-            //     // allow the managed pointer to be NULL
-            //     unsafe {
-            //         // For each column (that we are using), put the newest edges at the front.
-            //         rotate_left((*table_ptr).edges.parent, num_edges, offset);
-            //         rotate_left((*table_ptr).edges.child, num_edges, offset);
-            //         rotate_left((*table_ptr).edges.left, num_edges, offset);
-            //         rotate_left((*table_ptr).edges.right, num_edges, offset);
-            //     }
-            // }
-            let idmap = match self.tables.simplify(
-                &self.alive_nodes,
-                tskit::SimplificationOptions::default(),
-                true,
-            ) {
-                Err(e) => return Err(Box::new(e)),
-                Ok(x) => x.unwrap(),
+            match self.edge_strategy {
+                EdgeStrategy::Buffer => self.flush_edge_buffer()?,
+                EdgeStrategy::IncrementalSort | EdgeStrategy::FullSort => self.sort_edges()?,
+            }
+
+            // A preserved node may still be alive.
+            let mut samples = self.alive_nodes.clone();
+            let alive = self
+                .alive_nodes
+                .iter()
+                .collect::<std::collections::HashSet<_>>();
+            samples.extend(self.preserved_nodes.iter().filter(|n| !alive.contains(n)));
+
+            let idmap = {
+                ancestry_common::phase!("simplify_tables");
+                match self
+                    .tables
+                    .simplify(&samples, self.simplification_options, true)
+                {
+                    Err(e) => return Err(Box::new(e)),
+                    Ok(x) => x.unwrap(),
+                }
             };
             self.last_time_simplified = Some(current_time_point);
 
             // next time, we will only sort the new edges
-            // TODO: try to restore this
-            // self.bookmark.offsets.edges = u64::from(self.tables.edges().num_rows());
+            self.bookmark.offsets.edges = u64::from(self.tables.edges().num_rows());
+
+            self.num_sorted_mutations = u64::from(self.tables.mutations().num_rows());
+
+            // Simplification removes and renumbers sites
+            self.sites.clear();
+            for site in self.tables.sites_iter() {
+                self.sites
+                    .insert(f64::from(site.position).to_bits(), site.id);
+            }
 
             // remap the alive nodes
             for alive in self.alive_nodes.iter_mut() {
@@ -106,12 +462,42 @@ impl EvolvableTableCollection {
                 assert!(!alive.is_null());
             }
 
+            for preserved in self.preserved_nodes.iter_mut() {
+                *preserved = idmap[usize::from(*preserved)];
+                assert!(!preserved.is_null());
+            }
+
+            // and every node that we still have
+            self.mapped_nodes.retain(|i| {
+                let id = &mut self.idmap[*i];
+                *id = idmap[usize::from(*id)];
+                !id.is_null()
+            });
+
             let num_samples = self
                 .tables
                 .nodes_iter()
                 .filter(|n| n.flags.contains(tskit::NodeFlags::IS_SAMPLE))
                 .count();
-            assert_eq!(num_samples as i32, self.popsize);
+            if num_samples != samples.len() {
+                return Err(Box::new(tskit::TskitError::ValueError {
+                    got: format!("{} sample nodes after simplifying", num_samples),
+                    expected: format!("{} (the alive and preserved nodes)", samples.len()),
+                }));
+            }
+
+            if let Some(adaptive_interval) = self.adaptive_interval.as_mut() {
+                adaptive_interval.update(
+                    start.elapsed(),
+                    since_last,
+                    edge_rows_kept,
+                    edge_rows_added,
+                );
+            }
+
+            if let Some(hook) = self.simplification_hook.as_mut() {
+                hook.simplified(current_time_point, &self.tables, &idmap);
+            }
             Ok(())
         } else {
             Ok(())
@@ -146,15 +532,7 @@ impl EvolveAncestry for EvolvableTableCollection {
 
     fn setup(&mut self, final_time: LargeSignedInteger) {
         for i in 0..self.current_population_size() {
-            let id = self
-                .tables
-                .add_node(
-                    0,
-                    Time::from(final_time as f64),
-                    PopulationId::NULL,
-                    IndividualId::NULL,
-                )
-                .unwrap();
+            let id = self.add_node(Time::from(final_time as f64), &[]).unwrap();
             self.alive_nodes.push(id);
         }
     }
@@ -180,9 +558,14 @@ impl EvolveAncestry for EvolvableTableCollection {
         breakpoints: &[neutral_evolution::TransmittedSegment],
     ) -> Result<(), Box<dyn std::error::Error>> {
         let t = Time::from((final_timepoint - birth_time) as f64);
-        let child = self
-            .tables
-            .add_node(0, t, PopulationId::NULL, IndividualId::NULL)?;
+        let mut parents = vec![];
+        for b in breakpoints {
+            let parent = self.tables.nodes().individual(self.alive_nodes[b.parent])?;
+            if !parents.contains(&parent) {
+                parents.push(parent);
+            }
+        }
+        let child = self.add_node(t, &parents)?;
         for b in breakpoints {
             #[cfg(debug_assertions)]
             {
@@ -201,18 +584,57 @@ impl EvolveAncestry for EvolvableTableCollection {
                     birth_time
                 );
             }
-            self.tables.add_edge(
-                Position::from(b.left as f64),
-                Position::from(b.right as f64),
-                self.alive_nodes[b.parent],
-                child,
-            )?;
+            let left = Position::from(b.left as f64);
+            let right = Position::from(b.right as f64);
+            let parent = self.alive_nodes[b.parent];
+            match self.edge_strategy {
+                EdgeStrategy::Buffer => self.buffer_edge(left, right, parent, child),
+                EdgeStrategy::IncrementalSort | EdgeStrategy::FullSort => {
+                    self.tables.add_edge(left, right, parent, child)?;
+                }
+            }
         }
         self.births.push(child);
 
         Ok(())
     }
 
+    fn record_mutations(
+        &mut self,
+        birth_time: LargeSignedInteger,
+        final_timepoint: LargeSignedInteger,
+        mutations: &[neutral_evolution::Mutation],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let t = Time::from((final_timepoint - birth_time) as f64);
+        let child = *self
+            .births
+            .last()
+            .ok_or("mutations recorded before any birth")?;
+        for m in mutations {
+            let site = match self.sites.get(&m.position.to_bits()) {
+                Some(site) => *site,
+                None => {
+                    let site = self.tables.add_site(m.position, Some(b"0"))?;
+                    self.sites.insert(m.position.to_bits(), site);
+                    site
+                }
+            };
+            self.tables
+                .add_mutation(site, child, MutationId::NULL, t, Some(b"1"))?;
+        }
+        Ok(())
+    }
+
+    fn record_provenance(
+        &mut self,
+        provenance: &neutral_evolution::Provenance,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.tables.add_provenance(
+            &provenance.to_json(env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")),
+        )?;
+        Ok(())
+    }
+
     fn simplify(
         &mut self,
         current_time_point: LargeSignedInteger,
@@ -234,7 +656,7 @@ impl EvolveAncestry for EvolvableTableCollection {
             }
             None => self.simplify_details(current_time_point, true),
         };
-        for i in self.alive_nodes.iter() {
+        for i in self.alive_nodes.iter().chain(self.preserved_nodes.iter()) {
             self.tables.nodes().flags_array_mut()[usize::from(*i)] = tskit::NodeFlags::IS_SAMPLE;
         }
         rv
@@ -0,0 +1,264 @@
+//! Place neutral mutations on an existing genealogy.
+//!
+//! Because neutral mutations do not affect the genealogy,
+//! they can be added after a simulation has finished.
+//! Mutations are Poisson along each edge, with mean equal to
+//! rate × branch length × span, and follow an infinite-sites
+//! model, so that each one gets a new site.
+
+use rand::distributions::Distribution;
+use rand::SeedableRng;
+use thiserror::Error;
+use tskit::prelude::*;
+use tskit::TableCollection;
+
+#[derive(Error, Debug)]
+pub enum MutationError {
+    #[error("invalid mutation rate: {rate:?}")]
+    InvalidRate { rate: f64 },
+    #[error("invalid rate map: {0}")]
+    InvalidRateMap(String),
+    #[error("rate map length {map:?} does not match sequence length {tables:?}")]
+    RateMapLength { map: f64, tables: f64 },
+    #[error("{0:?}")]
+    TskitError(tskit::TskitError),
+}
+
+impl From<tskit::TskitError> for MutationError {
+    fn from(e: tskit::TskitError) -> Self {
+        Self::TskitError(e)
+    }
+}
+
+/// Piecewise-constant relative mutation rates.
+///
+/// The rate on `[positions[i], positions[i + 1])` is `rates[i]`.
+#[derive(Clone, Debug)]
+pub struct RateMap {
+    positions: Vec<f64>,
+    rates: Vec<f64>,
+}
+
+impl RateMap {
+    /// # Errors
+    ///
+    /// `positions` must start at zero and be strictly increasing,
+    /// with one more element than `rates`.
+    /// Rates must be finite and non-negative.
+    pub fn new(positions: Vec<f64>, rates: Vec<f64>) -> Result<Self, MutationError> {
+        if positions.len() != rates.len() + 1 || rates.is_empty() {
+            return Err(MutationError::InvalidRateMap(
+                "there must be one more position than rates".to_string(),
+            ));
+        }
+        if positions[0] != 0.0 {
+            return Err(MutationError::InvalidRateMap(
+                "the first position must be 0".to_string(),
+            ));
+        }
+        if !positions.windows(2).all(|w| w[0] < w[1]) || !positions.iter().all(|p| p.is_finite()) {
+            return Err(MutationError::InvalidRateMap(
+                "positions must be finite and strictly increasing".to_string(),
+            ));
+        }
+        if !rates.iter().all(|r| r.is_finite() && *r >= 0.0) {
+            return Err(MutationError::InvalidRateMap(
+                "rates must be finite and >= 0".to_string(),
+            ));
+        }
+        Ok(Self { positions, rates })
+    }
+
+    /// The same rate everywhere.
+    pub fn uniform(sequence_length: f64, rate: f64) -> Result<Self, MutationError> {
+        Self::new(vec![0.0, sequence_length], vec![rate])
+    }
+
+    pub fn sequence_length(&self) -> f64 {
+        *self.positions.last().unwrap()
+    }
+
+    // (left, right, rate) for each interval overlapping [left, right)
+    fn intervals(&self, left: f64, right: f64) -> impl Iterator<Item = (f64, f64, f64)> + '_ {
+        let first = self.positions.partition_point(|p| *p <= left) - 1;
+        self.positions[first..]
+            .windows(2)
+            .zip(self.rates[first..].iter())
+            .take_while(move |(w, _)| w[0] < right)
+            .map(move |(w, r)| (f64::max(w[0], left), f64::min(w[1], right), *r))
+    }
+}
+
+/// Add neutral mutations to `tables`.
+///
+/// The mutation rate is per unit of genome length per unit of time.
+/// If `rate_map` is given, the rate on each of its intervals
+/// is multiplied by the map's value.
+///
+/// New sites have ancestral state `0` and new mutations
+/// have derived state `1`.
+/// Existing sites and mutations are kept.
+/// The tables are sorted and indexed on return.
+///
+/// Returns the number of mutations added.
+///
+/// # Errors
+///
+/// [MutationError] if the rate is not finite and non-negative,
+/// if the rate map does not cover the genome, or if tskit
+/// returns an error.
+pub fn place_mutations(
+    tables: &mut TableCollection,
+    rate: f64,
+    rate_map: Option<&RateMap>,
+    seed: u64,
+) -> Result<usize, MutationError> {
+    if !rate.is_finite() || rate < 0.0 {
+        return Err(MutationError::InvalidRate { rate });
+    }
+    let sequence_length = f64::from(tables.sequence_length());
+    let uniform_map;
+    let rate_map = match rate_map {
+        Some(map) => {
+            if map.sequence_length() != sequence_length {
+                return Err(MutationError::RateMapLength {
+                    map: map.sequence_length(),
+                    tables: sequence_length,
+                });
+            }
+            map
+        }
+        None => {
+            uniform_map = RateMap::uniform(sequence_length, 1.0)?;
+            &uniform_map
+        }
+    };
+
+    let mut rng = rand_pcg::Pcg64::seed_from_u64(seed);
+    let times = tables
+        .nodes_iter()
+        .map(|n| f64::from(n.time))
+        .collect::<Vec<_>>();
+
+    // (position, time, node)
+    let mut mutations = vec![];
+    for edge in tables.edges_iter() {
+        let child_time = times[usize::from(edge.child)];
+        let parent_time = times[usize::from(edge.parent)];
+        let branch_length = parent_time - child_time;
+        for (left, right, relative_rate) in
+            rate_map.intervals(f64::from(edge.left), f64::from(edge.right))
+        {
+            let mean = rate * relative_rate * branch_length * (right - left);
+            // rand_distr::Poisson requires a mean > 0
+            if mean <= 0.0 {
+                continue;
+            }
+            let n = rand_distr::Poisson::new(mean).unwrap().sample(&mut rng) as u64;
+            let position = rand_distr::Uniform::new(left, right);
+            let time = rand_distr::Uniform::new(child_time, parent_time);
+            for _ in 0..n {
+                mutations.push((position.sample(&mut rng), time.sample(&mut rng), edge.child));
+            }
+        }
+    }
+
+    for (position, time, node) in mutations.iter() {
+        let site = tables.add_site(*position, Some(b"0"))?;
+        tables.add_mutation(site, *node, MutationId::NULL, *time, Some(b"1"))?;
+    }
+
+    tables.full_sort(tskit::TableSortOptions::default())?;
+    tables.build_index()?;
+
+    Ok(mutations.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A single edge spanning the whole genome
+    fn single_edge(sequence_length: f64, branch_length: f64) -> TableCollection {
+        let mut tables = TableCollection::new(sequence_length).unwrap();
+        let parent = tables
+            .add_node(0, branch_length, PopulationId::NULL, IndividualId::NULL)
+            .unwrap();
+        let child = tables
+            .add_node(
+                tskit::NodeFlags::IS_SAMPLE,
+                0.0,
+                PopulationId::NULL,
+                IndividualId::NULL,
+            )
+            .unwrap();
+        tables.add_edge(0., sequence_length, parent, child).unwrap();
+        tables
+    }
+
+    #[test]
+    fn test_rate_map_validation() {
+        assert!(RateMap::new(vec![0.0, 1.0], vec![]).is_err());
+        assert!(RateMap::new(vec![1.0, 2.0], vec![1.0]).is_err());
+        assert!(RateMap::new(vec![0.0, 2.0, 1.0], vec![1.0, 1.0]).is_err());
+        assert!(RateMap::new(vec![0.0, 1.0], vec![-1.0]).is_err());
+        assert!(RateMap::new(vec![0.0, 1.0, 2.0], vec![1.0, 0.0]).is_ok());
+    }
+
+    #[test]
+    fn test_rate_map_intervals() {
+        let map = RateMap::new(vec![0.0, 10.0, 20.0, 30.0], vec![1.0, 2.0, 3.0]).unwrap();
+        let intervals = map.intervals(5.0, 20.0).collect::<Vec<_>>();
+        assert_eq!(intervals, vec![(5.0, 10.0, 1.0), (10.0, 20.0, 2.0)]);
+        let intervals = map.intervals(20.0, 25.0).collect::<Vec<_>>();
+        assert_eq!(intervals, vec![(20.0, 25.0, 3.0)]);
+    }
+
+    #[test]
+    fn test_zero_rate() {
+        let mut tables = single_edge(100.0, 10.0);
+        assert_eq!(place_mutations(&mut tables, 0.0, None, 101).unwrap(), 0);
+        assert!(place_mutations(&mut tables, -1.0, None, 101).is_err());
+    }
+
+    #[test]
+    fn test_mutations_are_on_branches() {
+        let mut tables = single_edge(100.0, 10.0);
+        let n = place_mutations(&mut tables, 1e-1, None, 101).unwrap();
+        // Mean is 100
+        assert!(n > 50 && n < 150, "{}", n);
+        assert_eq!(u64::from(tables.mutations().num_rows()), n as u64);
+        assert_eq!(u64::from(tables.sites().num_rows()), n as u64);
+        for m in tables.mutations_iter() {
+            let t = f64::from(m.time);
+            assert!((0.0..10.0).contains(&t));
+            assert_eq!(usize::from(m.node), 1);
+        }
+    }
+
+    #[test]
+    fn test_rate_map() {
+        let mut tables = single_edge(100.0, 10.0);
+        let map = RateMap::new(vec![0.0, 50.0, 100.0], vec![0.0, 1.0]).unwrap();
+        let n = place_mutations(&mut tables, 1e-1, Some(&map), 101).unwrap();
+        assert!(n > 0);
+        assert!(tables.sites_iter().all(|s| f64::from(s.position) >= 50.0));
+
+        let bad_map = RateMap::uniform(50.0, 1.0).unwrap();
+        assert!(place_mutations(&mut tables, 1e-1, Some(&bad_map), 101).is_err());
+    }
+
+    #[test]
+    fn test_same_seed_same_mutations() {
+        let mut a = single_edge(100.0, 10.0);
+        let mut b = single_edge(100.0, 10.0);
+        place_mutations(&mut a, 1e-2, None, 42).unwrap();
+        place_mutations(&mut b, 1e-2, None, 42).unwrap();
+        let positions = |t: &TableCollection| {
+            t.sites_iter()
+                .map(|s| f64::from(s.position))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(positions(&a), positions(&b));
+    }
+}
@@ -0,0 +1,34 @@
+use ancestry_common::LargeSignedInteger;
+use tskit::{NodeId, TableCollection};
+
+/// Called after each simplification of an
+/// [EvolvableTableCollection](crate::EvolvableTableCollection).
+///
+/// Implemented for closures taking the same arguments as
+/// [simplified](SimplificationHook::simplified).
+pub trait SimplificationHook {
+    /// * `current_time_point`: the time step that was simplified.
+    /// * `tables`: the simplified tables.
+    /// * `idmap`: the output ID of each input node of this
+    ///   simplification, or [NodeId::NULL] if it was removed.
+    fn simplified(
+        &mut self,
+        current_time_point: LargeSignedInteger,
+        tables: &TableCollection,
+        idmap: &[NodeId],
+    );
+}
+
+impl<F> SimplificationHook for F
+where
+    F: FnMut(LargeSignedInteger, &TableCollection, &[NodeId]),
+{
+    fn simplified(
+        &mut self,
+        current_time_point: LargeSignedInteger,
+        tables: &TableCollection,
+        idmap: &[NodeId],
+    ) {
+        self(current_time_point, tables, idmap)
+    }
+}
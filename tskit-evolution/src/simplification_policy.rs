@@ -0,0 +1,173 @@
+//! When [EvolvableTableCollection](crate::EvolvableTableCollection)
+//! simplifies its tables.
+
+use ancestry_common::LargeSignedInteger;
+use std::time::Duration;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum SimplificationPolicyError {
+    #[error("simplification interval must be > 0, got {0:?}")]
+    Interval(LargeSignedInteger),
+    #[error("edge row threshold must be > 0")]
+    EdgeRows,
+    #[error("memory budget must be > 0")]
+    MemoryBudget,
+    #[error("adaptive intervals must satisfy 0 < initial <= max, got {initial:?} and {max:?}")]
+    Adaptive {
+        initial: LargeSignedInteger,
+        max: LargeSignedInteger,
+    },
+}
+
+/// When to simplify, in addition to when the simulation finishes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SimplificationPolicy {
+    /// Every `n` time steps.
+    Interval(LargeSignedInteger),
+    /// Once the edge table, including any buffered
+    /// edges, has at least this many rows.
+    EdgeRows(u64),
+    /// Once the estimated size of the table columns
+    /// is at least this many bytes.
+    MemoryBudget(usize),
+    /// Every `n` time steps, where `n` starts at `initial_interval`
+    /// and is tuned after each simplification, within `[1, max_interval]`.
+    ///
+    /// Longer intervals spend less time simplifying per step but let
+    /// more edges accumulate, so the tuning reduces the product of the
+    /// time spent simplifying per step and the number of edge rows
+    /// held just before simplifying.
+    Adaptive {
+        initial_interval: LargeSignedInteger,
+        max_interval: LargeSignedInteger,
+    },
+}
+
+impl SimplificationPolicy {
+    pub(crate) fn validate(&self) -> Result<(), SimplificationPolicyError> {
+        match *self {
+            Self::Interval(n) if n < 1 => Err(SimplificationPolicyError::Interval(n)),
+            Self::EdgeRows(0) => Err(SimplificationPolicyError::EdgeRows),
+            Self::MemoryBudget(0) => Err(SimplificationPolicyError::MemoryBudget),
+            Self::Adaptive {
+                initial_interval,
+                max_interval,
+            } if initial_interval < 1 || initial_interval > max_interval => {
+                Err(SimplificationPolicyError::Adaptive {
+                    initial: initial_interval,
+                    max: max_interval,
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+// Hill-climbs the interval: keep changing it in the same
+// direction while the objective falls, and turn around when it rises.
+// The objective is the simplification time per step times the edge rows
+// held before simplifying. The time per step only falls as the interval
+// grows, so on its own it would always push the interval to the max.
+#[derive(Debug)]
+pub(crate) struct AdaptiveInterval {
+    pub(crate) interval: LargeSignedInteger,
+    max_interval: LargeSignedInteger,
+    growing: bool,
+    last_objective: Option<f64>,
+}
+
+impl AdaptiveInterval {
+    pub(crate) fn new(
+        initial_interval: LargeSignedInteger,
+        max_interval: LargeSignedInteger,
+    ) -> Self {
+        Self {
+            interval: initial_interval,
+            max_interval,
+            growing: true,
+            last_objective: None,
+        }
+    }
+
+    // `edge_rows_kept`: the edge rows left by the previous simplification.
+    // `edge_rows_added`: those added since then, over `steps`.
+    pub(crate) fn update(
+        &mut self,
+        cost: Duration,
+        steps: LargeSignedInteger,
+        edge_rows_kept: u64,
+        edge_rows_added: u64,
+    ) {
+        let cost_per_step = cost.as_secs_f64() / (steps.max(1) as f64);
+        let objective = cost_per_step * ((edge_rows_kept + edge_rows_added).max(1) as f64);
+        if let Some(last) = self.last_objective {
+            if objective > last {
+                self.growing = !self.growing;
+            }
+        }
+        self.last_objective = Some(objective);
+        let change = std::cmp::max(1, self.interval / 4);
+        self.interval = if self.growing {
+            self.interval + change
+        } else {
+            self.interval - change
+        }
+        .clamp(1, self.max_interval);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        assert!(SimplificationPolicy::Interval(1).validate().is_ok());
+        assert!(SimplificationPolicy::Interval(0).validate().is_err());
+        assert!(SimplificationPolicy::EdgeRows(0).validate().is_err());
+        assert!(SimplificationPolicy::MemoryBudget(0).validate().is_err());
+        assert!(SimplificationPolicy::Adaptive {
+            initial_interval: 10,
+            max_interval: 5
+        }
+        .validate()
+        .is_err());
+        assert!(SimplificationPolicy::Adaptive {
+            initial_interval: 5,
+            max_interval: 5
+        }
+        .validate()
+        .is_ok());
+    }
+
+    #[test]
+    fn test_adaptive_interval() {
+        let mut a = AdaptiveInterval::new(8, 10);
+        a.update(Duration::from_millis(8), 8, 100, 80);
+        assert_eq!(a.interval, 10);
+        // Cheaper per step, so keep growing, up to the max.
+        a.update(Duration::from_millis(5), 10, 100, 100);
+        assert_eq!(a.interval, 10);
+        // More expensive, so turn around.
+        a.update(Duration::from_millis(20), 10, 100, 100);
+        assert_eq!(a.interval, 8);
+        a.update(Duration::from_millis(1), 8, 100, 80);
+        assert_eq!(a.interval, 6);
+        for _ in 0..100 {
+            a.update(Duration::from_millis(0), 1, 100, 10);
+        }
+        assert_eq!(a.interval, 1);
+    }
+
+    #[test]
+    fn test_adaptive_interval_counts_edge_rows() {
+        let mut a = AdaptiveInterval::new(8, 100);
+        a.update(Duration::from_millis(8), 8, 100, 80);
+        assert_eq!(a.interval, 10);
+        // Less time per step, but the tables grew by more,
+        // so turn around.
+        a.update(Duration::from_millis(9), 10, 100, 300);
+        assert_eq!(a.interval, 8);
+    }
+}
@@ -0,0 +1,379 @@
+//! Write the genotypes of sample nodes as VCF.
+//!
+//! Samples are taken in node table order, which is
+//! population order for both backends.
+//! Consecutive groups of `ploidy` samples form an individual.
+//! The simulations are haploid, so this grouping only sets
+//! the layout of the output: the grouped genomes are not
+//! related to each other in any particular way.
+//!
+//! Positions are the integer coordinates of the simulated genome,
+//! plus one because VCF is 1-based. Sites at continuous positions
+//! are rounded down, and sites that round to the same position
+//! are written as one record, as if the mutations had all
+//! happened at that position. Each sample then has the allele
+//! of the youngest mutation above it at any of those sites.
+//! Alleles are written exactly as stored in the site
+//! and mutation tables.
+
+use std::collections::HashMap;
+use std::io::Write;
+use thiserror::Error;
+use tskit::prelude::*;
+use tskit::TableCollection;
+
+#[derive(Error, Debug)]
+pub enum VcfError {
+    #[error("{num_samples:?} samples cannot be split into individuals of ploidy {ploidy:?}")]
+    Ploidy { num_samples: usize, ploidy: usize },
+    #[error("sites at position {position:?} have different ancestral states")]
+    AncestralStates { position: i64 },
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+    #[error("{0:?}")]
+    TskitError(tskit::TskitError),
+}
+
+impl From<tskit::TskitError> for VcfError {
+    fn from(e: tskit::TskitError) -> Self {
+        Self::TskitError(e)
+    }
+}
+
+struct Site {
+    position: f64,
+    ancestral_state: String,
+    // (node, time, derived state), oldest first
+    mutations: Vec<(NodeId, f64, String)>,
+}
+
+fn state(s: Option<Vec<u8>>) -> String {
+    String::from_utf8_lossy(&s.unwrap_or_default()).into_owned()
+}
+
+fn collect_sites(tables: &TableCollection) -> Vec<Site> {
+    let mut sites = tables
+        .sites_iter()
+        .map(|s| Site {
+            position: f64::from(s.position),
+            ancestral_state: state(s.ancestral_state),
+            mutations: vec![],
+        })
+        .collect::<Vec<_>>();
+    // Sorted tables list older mutations at a site first.
+    for m in tables.mutations_iter() {
+        sites[usize::from(m.site)].mutations.push((
+            m.node,
+            f64::from(m.time),
+            state(m.derived_state),
+        ));
+    }
+    sites
+}
+
+// The 1-based VCF position of a site.
+fn vcf_position(position: f64) -> i64 {
+    position.floor() as i64 + 1
+}
+
+// One line of the VCF, from one or more sites.
+struct Record<'a> {
+    position: i64,
+    alleles: Vec<&'a str>,
+    // For each sample, the time and allele of the youngest
+    // mutation above it, if any.
+    carried: Vec<Option<(f64, usize)>>,
+}
+
+impl<'a> Record<'a> {
+    fn new(site: &'a Site, num_samples: usize) -> Self {
+        Self {
+            position: vcf_position(site.position),
+            alleles: vec![site.ancestral_state.as_str()],
+            carried: vec![None; num_samples],
+        }
+    }
+
+    fn allele(&mut self, state: &'a str) -> usize {
+        match self.alleles.iter().position(|a| *a == state) {
+            Some(a) => a,
+            None => {
+                self.alleles.push(state);
+                self.alleles.len() - 1
+            }
+        }
+    }
+
+    // Adds the mutations at `site`, on the local tree given by `parents`.
+    fn add_site(&mut self, site: &'a Site, samples: &[NodeId], parents: &[NodeId]) {
+        // The youngest mutation on a node wins.
+        let mut allele_on = HashMap::new();
+        for (node, time, derived_state) in site.mutations.iter() {
+            let allele = self.allele(derived_state);
+            allele_on.insert(*node, (*time, allele));
+        }
+
+        // Each sample has the allele of the nearest mutated node above it.
+        for (carried, s) in self.carried.iter_mut().zip(samples.iter()) {
+            let mut u = *s;
+            while !u.is_null() {
+                if let Some((time, allele)) = allele_on.get(&u) {
+                    if !matches!(carried, Some(c) if c.0 <= *time) {
+                        *carried = Some((*time, *allele));
+                    }
+                    break;
+                }
+                u = parents[usize::from(u)];
+            }
+        }
+    }
+
+    fn write<W: Write>(&self, ploidy: usize, writer: &mut W) -> Result<(), VcfError> {
+        let alt = if self.alleles.len() > 1 {
+            self.alleles[1..].join(",")
+        } else {
+            ".".to_string()
+        };
+        write!(
+            writer,
+            "1\t{}\t.\t{}\t{}\t.\tPASS\t.\tGT",
+            self.position, self.alleles[0], alt
+        )?;
+        for individual in self.carried.chunks(ploidy) {
+            let gt = individual
+                .iter()
+                .map(|c| c.map_or(0, |c| c.1).to_string())
+                .collect::<Vec<_>>()
+                .join("|");
+            write!(writer, "\t{}", gt)?;
+        }
+        writeln!(writer)?;
+        Ok(())
+    }
+}
+
+fn write_header<W: Write>(
+    sequence_length: f64,
+    num_individuals: usize,
+    writer: &mut W,
+) -> Result<(), VcfError> {
+    writeln!(writer, "##fileformat=VCFv4.2")?;
+    writeln!(writer, "##source=tskit-evolution")?;
+    writeln!(writer, "##contig=<ID=1,length={}>", sequence_length as i64)?;
+    writeln!(
+        writer,
+        "##FORMAT=<ID=GT,Number=1,Type=String,Description=\"Genotype\">"
+    )?;
+    write!(
+        writer,
+        "#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\tFORMAT"
+    )?;
+    for i in 0..num_individuals {
+        write!(writer, "\ttsk_{}", i)?;
+    }
+    writeln!(writer)?;
+    Ok(())
+}
+
+/// Write the genotypes of all sample nodes in `tables` as VCF.
+///
+/// # Errors
+///
+/// [VcfError::Ploidy] if `ploidy` is zero or does not divide the
+/// number of samples, [VcfError::AncestralStates] if sites with
+/// the same VCF position have different ancestral states,
+/// or any error from tskit or from `writer`.
+/// Nothing is written if the tables have either of the first two problems.
+pub fn write_vcf<W: Write>(
+    tables: &TableCollection,
+    ploidy: usize,
+    writer: &mut W,
+) -> Result<(), VcfError> {
+    let samples = tables
+        .nodes_iter()
+        .filter(|n| n.flags.is_sample())
+        .map(|n| n.id)
+        .collect::<Vec<_>>();
+    if ploidy == 0 || samples.len() % ploidy != 0 {
+        return Err(VcfError::Ploidy {
+            num_samples: samples.len(),
+            ploidy,
+        });
+    }
+
+    let sites = collect_sites(tables);
+    // Sorted tables have sites in position order.
+    if let Some(w) = sites.windows(2).find(|w| {
+        vcf_position(w[0].position) == vcf_position(w[1].position)
+            && w[0].ancestral_state != w[1].ancestral_state
+    }) {
+        return Err(VcfError::AncestralStates {
+            position: vcf_position(w[0].position),
+        });
+    }
+
+    let sequence_length = f64::from(tables.sequence_length());
+    write_header(sequence_length, samples.len() / ploidy, writer)?;
+
+    let treeseq = tables
+        .deepcopy()?
+        .tree_sequence(tskit::TreeSequenceFlags::BUILD_INDEXES)?;
+    let mut trees = treeseq.tree_iterator(tskit::TreeFlags::default())?;
+    let mut site_iter = sites.iter().peekable();
+    let mut record: Option<Record> = None;
+
+    // Sites with the same VCF position may be on different trees.
+    while let Some(tree) = trees.next() {
+        let right = f64::from(tree.interval().1);
+        let parents = tree.parent_array();
+        while let Some(site) = site_iter.next_if(|s| s.position < right) {
+            match record.as_mut() {
+                Some(r) if r.position == vcf_position(site.position) => {}
+                _ => {
+                    if let Some(r) = record.replace(Record::new(site, samples.len())) {
+                        r.write(ploidy, writer)?;
+                    }
+                }
+            }
+            record.as_mut().unwrap().add_site(site, &samples, parents);
+        }
+    }
+    if let Some(r) = record {
+        r.write(ploidy, writer)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // ((0, 1), 2), with a mutation above 0 at position 2.5
+    // and above (0, 1) at position 7.0
+    fn make_tables() -> TableCollection {
+        let mut tables = TableCollection::new(10.).unwrap();
+        let mut samples = vec![];
+        for _ in 0..4 {
+            samples.push(
+                tables
+                    .add_node(
+                        tskit::NodeFlags::IS_SAMPLE,
+                        0.0,
+                        PopulationId::NULL,
+                        IndividualId::NULL,
+                    )
+                    .unwrap(),
+            );
+        }
+        let a = tables
+            .add_node(0, 1.0, PopulationId::NULL, IndividualId::NULL)
+            .unwrap();
+        let root = tables
+            .add_node(0, 2.0, PopulationId::NULL, IndividualId::NULL)
+            .unwrap();
+        tables.add_edge(0., 10., a, samples[0]).unwrap();
+        tables.add_edge(0., 10., a, samples[1]).unwrap();
+        tables.add_edge(0., 10., root, a).unwrap();
+        tables.add_edge(0., 10., root, samples[2]).unwrap();
+        tables.add_edge(0., 10., root, samples[3]).unwrap();
+        let site = tables.add_site(2.5, Some(b"0")).unwrap();
+        tables
+            .add_mutation(site, samples[0], MutationId::NULL, 0.5, Some(b"1"))
+            .unwrap();
+        let site = tables.add_site(7.0, Some(b"0")).unwrap();
+        tables
+            .add_mutation(site, a, MutationId::NULL, 1.5, Some(b"1"))
+            .unwrap();
+        tables
+            .full_sort(tskit::TableSortOptions::default())
+            .unwrap();
+        tables
+    }
+
+    fn to_string(tables: &TableCollection, ploidy: usize) -> String {
+        let mut buffer = vec![];
+        write_vcf(tables, ploidy, &mut buffer).unwrap();
+        String::from_utf8(buffer).unwrap()
+    }
+
+    #[test]
+    fn test_haploid() {
+        let vcf = to_string(&make_tables(), 1);
+        let lines = vcf.lines().collect::<Vec<_>>();
+        assert!(lines.contains(&"##contig=<ID=1,length=10>"));
+        assert!(lines[4].ends_with("FORMAT\ttsk_0\ttsk_1\ttsk_2\ttsk_3"));
+        assert_eq!(lines[5], "1\t3\t.\t0\t1\t.\tPASS\t.\tGT\t1\t0\t0\t0");
+        assert_eq!(lines[6], "1\t8\t.\t0\t1\t.\tPASS\t.\tGT\t1\t1\t0\t0");
+        assert_eq!(lines.len(), 7);
+    }
+
+    #[test]
+    fn test_diploid() {
+        let vcf = to_string(&make_tables(), 2);
+        let lines = vcf.lines().collect::<Vec<_>>();
+        assert!(lines[4].ends_with("FORMAT\ttsk_0\ttsk_1"));
+        assert!(lines[5].ends_with("GT\t1|0\t0|0"));
+        assert!(lines[6].ends_with("GT\t1|1\t0|0"));
+    }
+
+    // Adds a site at `position` with a mutation above
+    // the `n`-th sample, at `time`.
+    fn add_mutation(
+        tables: &mut TableCollection,
+        position: f64,
+        n: usize,
+        time: f64,
+        ancestral_state: &[u8],
+        derived_state: &[u8],
+    ) {
+        let sample = tables
+            .nodes_iter()
+            .filter(|n| n.flags.is_sample())
+            .nth(n)
+            .unwrap()
+            .id;
+        let site = tables.add_site(position, Some(ancestral_state)).unwrap();
+        tables
+            .add_mutation(site, sample, MutationId::NULL, time, Some(derived_state))
+            .unwrap();
+        tables
+            .full_sort(tskit::TableSortOptions::default())
+            .unwrap();
+    }
+
+    #[test]
+    fn test_sites_at_the_same_position() {
+        let mut tables = make_tables();
+        add_mutation(&mut tables, 2.9, 2, 0.5, b"0", b"1");
+        add_mutation(&mut tables, 2.1, 0, 0.25, b"0", b"2");
+        let vcf = to_string(&tables, 1);
+        let lines = vcf.lines().collect::<Vec<_>>();
+        // The mutation at 2.1 is younger than the one at 2.5.
+        assert_eq!(lines[5], "1\t3\t.\t0\t2,1\t.\tPASS\t.\tGT\t1\t0\t2\t0");
+        assert_eq!(lines[6], "1\t8\t.\t0\t1\t.\tPASS\t.\tGT\t1\t1\t0\t0");
+        assert_eq!(lines.len(), 7);
+    }
+
+    #[test]
+    fn test_conflicting_ancestral_states() {
+        let mut tables = make_tables();
+        add_mutation(&mut tables, 2.9, 2, 0.5, b"A", b"1");
+        let mut buffer = vec![];
+        assert!(matches!(
+            write_vcf(&tables, 1, &mut buffer),
+            Err(VcfError::AncestralStates { position: 3 })
+        ));
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn test_bad_ploidy() {
+        let mut buffer = vec![];
+        assert!(matches!(
+            write_vcf(&make_tables(), 3, &mut buffer),
+            Err(VcfError::Ploidy { .. })
+        ));
+        assert!(write_vcf(&make_tables(), 0, &mut buffer).is_err());
+    }
+}
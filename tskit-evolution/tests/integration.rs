@@ -1,11 +1,17 @@
-use neutral_evolution::{evolve, Parameters};
+use neutral_evolution::{evolve, EvolveAncestry, MutationModel, Parameters};
 use tskit::TableAccess;
 use tskit_evolution::*;
 
 #[test]
 fn test_simulation_round_trip() {
     for simplification_interval in 1..6_i64 {
-        let mut t = EvolvableTableCollection::new(100, 10, simplification_interval).unwrap();
+        let mut t = EvolvableTableCollection::new(
+            100,
+            10,
+            SimplificationPolicy::Interval(simplification_interval),
+            EdgeStrategy::IncrementalSort,
+        )
+        .unwrap();
         let p = Parameters::new(1.0, 1e-3, 100).unwrap();
         evolve(101, p, &mut t).unwrap();
         let ts = tskit::TreeSequence::try_from(t).unwrap();
@@ -17,7 +23,13 @@ fn test_simulation_round_trip() {
 fn test_simulation_round_trip_overlapping_gens() {
     for pdeath in [0.25, 0.5, 0.75, 0.9] {
         for simplification_interval in 1..6_i64 {
-            let mut t = EvolvableTableCollection::new(100, 10, simplification_interval).unwrap();
+            let mut t = EvolvableTableCollection::new(
+                100,
+                10,
+                SimplificationPolicy::Interval(simplification_interval),
+                EdgeStrategy::IncrementalSort,
+            )
+            .unwrap();
             let p = Parameters::new(pdeath, 1e-1, 100).unwrap();
             evolve(101, p, &mut t).unwrap();
             let ts = tskit::TreeSequence::try_from(t).unwrap();
@@ -25,3 +37,326 @@ fn test_simulation_round_trip_overlapping_gens() {
         }
     }
 }
+
+#[test]
+fn test_edge_strategies_give_identical_tables() {
+    let simulate = |pdeath, simplification_interval, edge_strategy| {
+        let p = Parameters::new(pdeath, 1e-1, 100)
+            .unwrap()
+            .with_mutations(1.0, MutationModel::FiniteSites)
+            .unwrap();
+        let mut t = EvolvableTableCollection::new(
+            100,
+            10,
+            SimplificationPolicy::Interval(simplification_interval),
+            edge_strategy,
+        )
+        .unwrap();
+        evolve(101, p, &mut t).unwrap();
+        tskit::TableCollection::from(t)
+    };
+    for pdeath in [1.0, 0.5, 0.1] {
+        for simplification_interval in [1, 2, 5, 100] {
+            let sorted = simulate(pdeath, simplification_interval, EdgeStrategy::FullSort);
+            for edge_strategy in [EdgeStrategy::IncrementalSort, EdgeStrategy::Buffer] {
+                let tables = simulate(pdeath, simplification_interval, edge_strategy);
+                assert!(
+                    tables.equals(&sorted, tskit::TableEqualityOptions::IGNORE_TIMESTAMPS),
+                    "{} {} {:?}",
+                    pdeath,
+                    simplification_interval,
+                    edge_strategy
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn test_full_sort_fallbacks() {
+    let simulate = |pdeath, edge_strategy| {
+        let p = Parameters::new(pdeath, 1.0, 100).unwrap();
+        let mut t = EvolvableTableCollection::new(
+            100,
+            10,
+            SimplificationPolicy::Interval(5),
+            edge_strategy,
+        )
+        .unwrap();
+        evolve(101, p, &mut t).unwrap();
+        t.num_full_sort_fallbacks()
+    };
+    // Every parent of a new edge is younger than every existing edge.
+    assert_eq!(simulate(1.0, EdgeStrategy::IncrementalSort), 0);
+    for pdeath in [0.5, 0.1] {
+        assert!(simulate(pdeath, EdgeStrategy::IncrementalSort) > 0);
+        assert_eq!(simulate(pdeath, EdgeStrategy::FullSort), 0);
+        assert_eq!(simulate(pdeath, EdgeStrategy::Buffer), 0);
+    }
+}
+
+#[test]
+fn test_simplification_policies_give_equivalent_genealogies() {
+    let simulate = |pdeath, simplification_policy, edge_strategy| {
+        let p = Parameters::new(pdeath, 1e-1, 100).unwrap();
+        let mut t =
+            EvolvableTableCollection::new(100, 10, simplification_policy, edge_strategy).unwrap();
+        evolve(101, p, &mut t).unwrap();
+        tskit::TableCollection::from(t)
+    };
+    for pdeath in [1.0, 0.5, 0.1] {
+        let expected = simulate(
+            pdeath,
+            SimplificationPolicy::Interval(1),
+            EdgeStrategy::IncrementalSort,
+        );
+        for simplification_policy in [
+            SimplificationPolicy::EdgeRows(50),
+            SimplificationPolicy::MemoryBudget(4096),
+            SimplificationPolicy::Adaptive {
+                initial_interval: 2,
+                max_interval: 20,
+            },
+        ] {
+            for edge_strategy in [EdgeStrategy::IncrementalSort, EdgeStrategy::Buffer] {
+                let tables = simulate(pdeath, simplification_policy, edge_strategy);
+                if let Err(e) = compare_genealogies_by_sample_order(&expected, &tables) {
+                    panic!(
+                        "{} {:?} {:?}: {}",
+                        pdeath, simplification_policy, edge_strategy, e
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_invalid_simplification_policies() {
+    for simplification_policy in [
+        SimplificationPolicy::Interval(0),
+        SimplificationPolicy::EdgeRows(0),
+        SimplificationPolicy::MemoryBudget(0),
+        SimplificationPolicy::Adaptive {
+            initial_interval: 0,
+            max_interval: 10,
+        },
+    ] {
+        assert!(EvolvableTableCollection::new(
+            100,
+            10,
+            simplification_policy,
+            EdgeStrategy::IncrementalSort
+        )
+        .is_err());
+    }
+}
+
+#[test]
+fn test_node_id_map() {
+    let simulate = |simplification_interval| {
+        let p = Parameters::new(0.5, 1e-1, 100).unwrap();
+        let mut t = EvolvableTableCollection::new(
+            100,
+            10,
+            SimplificationPolicy::Interval(simplification_interval),
+            EdgeStrategy::IncrementalSort,
+        )
+        .unwrap();
+        let simplifications = std::rc::Rc::new(std::cell::Cell::new(0));
+        let counter = simplifications.clone();
+        t.set_simplification_hook(Box::new(
+            move |_: i64, tables: &tskit::TableCollection, idmap: &[tskit::NodeId]| {
+                let num_mapped = idmap.iter().filter(|id| !id.is_null()).count();
+                assert_eq!(num_mapped as u64, u64::from(tables.nodes().num_rows()));
+                counter.set(counter.get() + 1);
+            },
+        ));
+        evolve(101, p, &mut t).unwrap();
+        assert!(t.take_simplification_hook().is_some());
+        let idmap = t.node_id_map().to_vec();
+        (
+            tskit::TableCollection::from(t),
+            idmap,
+            simplifications.get(),
+        )
+    };
+    let (expected, expected_idmap, _) = simulate(100);
+    for simplification_interval in [1, 7] {
+        let (tables, idmap, simplifications) = simulate(simplification_interval);
+        assert_eq!(
+            simplifications,
+            (100 + simplification_interval - 1) / simplification_interval
+        );
+        assert_eq!(idmap.len(), expected_idmap.len());
+        let mut num_mapped = 0;
+        for (id, expected_id) in idmap.iter().zip(expected_idmap.iter()) {
+            assert_eq!(id.is_null(), expected_id.is_null());
+            if !id.is_null() {
+                num_mapped += 1;
+                assert_eq!(
+                    tables.nodes().time(*id).unwrap(),
+                    expected.nodes().time(*expected_id).unwrap()
+                );
+            }
+        }
+        assert_eq!(num_mapped as u64, u64::from(tables.nodes().num_rows()));
+    }
+}
+
+// Preserves the alive nodes at the end of one time step.
+struct PreserveAliveNodes {
+    tables: EvolvableTableCollection,
+    time_point: i64,
+}
+
+impl EvolveAncestry for PreserveAliveNodes {
+    fn genome_length(&self) -> i64 {
+        self.tables.genome_length()
+    }
+
+    fn setup(&mut self, final_time: i64) {
+        self.tables.setup(final_time)
+    }
+
+    fn generate_deaths(&mut self, death: &mut neutral_evolution::Death) -> usize {
+        self.tables.generate_deaths(death)
+    }
+
+    fn current_population_size(&self) -> usize {
+        self.tables.current_population_size()
+    }
+
+    fn record_birth(
+        &mut self,
+        birth_time: i64,
+        final_timepoint: i64,
+        breakpoints: &[neutral_evolution::TransmittedSegment],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.tables
+            .record_birth(birth_time, final_timepoint, breakpoints)
+    }
+
+    fn record_provenance(
+        &mut self,
+        provenance: &neutral_evolution::Provenance,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.tables.record_provenance(provenance)
+    }
+
+    fn simplify(&mut self, current_time_point: i64) -> Result<(), Box<dyn std::error::Error>> {
+        self.tables.simplify(current_time_point)?;
+        if current_time_point == self.time_point {
+            let alive = self.tables.alive_nodes().to_vec();
+            self.tables.preserve_nodes(&alive)?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self, current_time_point: i64) -> Result<(), Box<dyn std::error::Error>> {
+        self.tables.finish(current_time_point)
+    }
+}
+
+#[test]
+fn test_preserved_nodes() {
+    for simplification_interval in [1, 7, 100] {
+        for options in [
+            tskit::SimplificationOptions::default(),
+            tskit::SimplificationOptions::KEEP_UNARY,
+            tskit::SimplificationOptions::KEEP_INPUT_ROOTS,
+        ] {
+            let mut t = EvolvableTableCollection::new(
+                100,
+                10,
+                SimplificationPolicy::Interval(simplification_interval),
+                EdgeStrategy::IncrementalSort,
+            )
+            .unwrap();
+            t.set_simplification_options(options);
+            let mut p = PreserveAliveNodes {
+                tables: t,
+                time_point: 50,
+            };
+            evolve(101, Parameters::new(0.5, 1e-1, 100).unwrap(), &mut p).unwrap();
+            let t = p.tables;
+            assert_eq!(t.preserved_nodes().len(), 10);
+            let mut samples = t.alive_nodes().to_vec();
+            samples.extend_from_slice(t.preserved_nodes());
+            samples.sort();
+            samples.dedup();
+            let tables = tskit::TableCollection::from(t);
+            let is_sample = |n: &tskit::NodeId| {
+                tables.nodes().flags(*n).unwrap() == tskit::NodeFlags::IS_SAMPLE
+            };
+            assert!(samples.iter().all(is_sample));
+            assert_eq!(
+                tables.nodes_iter().filter(|n| is_sample(&n.id)).count(),
+                samples.len()
+            );
+        }
+    }
+}
+
+#[test]
+fn test_filter_individuals_by_default() {
+    for options in [None, Some(tskit::SimplificationOptions::default())] {
+        let mut t = EvolvableTableCollection::new(
+            100,
+            10,
+            SimplificationPolicy::Interval(10),
+            EdgeStrategy::IncrementalSort,
+        )
+        .unwrap();
+        if let Some(options) = options {
+            t.set_simplification_options(options);
+        }
+        evolve(101, Parameters::new(1.0, 1e-1, 100).unwrap(), &mut t).unwrap();
+        let tables = tskit::TableCollection::from(t);
+        let num_individuals = u64::from(tables.individuals().num_rows());
+        match options {
+            // One individual per node.
+            None => assert_eq!(num_individuals, u64::from(tables.nodes().num_rows())),
+            // One individual per birth, plus the initial population.
+            Some(_) => assert_eq!(num_individuals, 10 + 100 * 10),
+        }
+    }
+}
+
+#[test]
+fn test_preserve_invalid_nodes() {
+    let mut t = EvolvableTableCollection::new(
+        100,
+        10,
+        SimplificationPolicy::Interval(1),
+        EdgeStrategy::IncrementalSort,
+    )
+    .unwrap();
+    t.setup(10);
+    assert!(t.preserve_nodes(&[tskit::NodeId::from(9)]).is_ok());
+    assert!(t.preserve_nodes(&[tskit::NodeId::from(10)]).is_err());
+    assert!(t.preserve_nodes(&[tskit::NodeId::NULL]).is_err());
+    assert_eq!(t.preserved_nodes(), &[tskit::NodeId::from(9)]);
+}
+
+#[test]
+fn test_place_mutations_after_simulation() {
+    let mut t = EvolvableTableCollection::new(
+        100,
+        10,
+        SimplificationPolicy::Interval(5),
+        EdgeStrategy::IncrementalSort,
+    )
+    .unwrap();
+    let p = Parameters::new(0.5, 1e-1, 100).unwrap();
+    evolve(101, p, &mut t).unwrap();
+    let mut tables = tskit::TableCollection::from(t);
+    let map = RateMap::new(vec![0.0, 50.0, 100.0], vec![1.0, 0.5]).unwrap();
+    let n = place_mutations(&mut tables, 1e-2, Some(&map), 101).unwrap();
+    assert!(n > 0);
+    assert_eq!(u64::from(tables.mutations().num_rows()), n as u64);
+    let ts = tables
+        .tree_sequence(tskit::TreeSequenceFlags::default())
+        .unwrap();
+    let _ = ts.dump_tables().unwrap();
+}